@@ -1,38 +1,72 @@
 mod input;
+mod solver;
 
+use std::env;
+use std::process;
 
-fn compute(noun: i32, verb: i32) -> i32 {
-    let mut instr = input::PROGRAM_INPUT;
-    instr[1] = noun;
-    instr[2] = verb;
-
-    let mut i_pointer = 0;
-    while instr[i_pointer] != 99 {
-        let a = instr[i_pointer+1] as usize;
-        let b = instr[i_pointer+2] as usize;
-        let target = instr[i_pointer+3] as usize;
-        if instr[i_pointer] == 1 {
-            instr[target] = instr[a] + instr[b];
-        } else if instr[i_pointer] == 2 {
-            instr[target] = instr[a] * instr[b];
-        } else {
-            println!("WRONG! {}", instr[i_pointer]);
-        }
-        i_pointer += 4;
-    }
-    instr[0]
+use intcode::intcode_cmp::IntCodeComputer;
+use intcode::intcode_error::IntcodeError;
+use intcode::intcode_parse::load_program;
+
+use crate::solver::{solve, SolverOptions};
+
+/// Run the program with noun and verb at addresses 1 and 2, and return the value at address 0.
+///
+/// * `program` - gravity assist program
+/// * `noun` - value for address 1
+/// * `verb` - value for address 2
+fn compute(program: &[i32], noun: i32, verb: i32) -> Result<i32, IntcodeError> {
+    let mut icc = IntCodeComputer::new(program);
+    icc.patch_memory(1, noun);
+    icc.patch_memory(2, verb);
+    icc.compute()?;
+    Ok(icc.memory().read(0))
 }
 
+/// Usage: advent-of-code-2019-2 [program-file]
+///
+/// Without a program file (`-` for stdin), the puzzle input is used.
 fn main() {
-    println!("Task 1 result: {}", compute(12, 02));
-
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let result = compute(noun, verb);
-            if result == 19690720 {
-                println!("noun: {}, verb: {}", noun, verb);
-                return;
-            }
-        }
+    let program: Vec<i32> = match env::args().nth(1) {
+        Some(path) => load_program(&path).unwrap_or_else(|err| {
+            eprintln!("Cannot load {}: {}", path, err);
+            process::exit(1);
+        }),
+        None => input::PROGRAM_INPUT.to_vec(),
+    };
+
+    match compute(&program, 12, 2) {
+        Ok(result) => println!("Task 1 result: {}", result),
+        Err(err) => println!("Task 1 failed: {}", err),
+    }
+
+    let solution = solve(&program, &SolverOptions::default());
+    match &solution.expression {
+        Ok(expression) => println!("Output: {}", expression),
+        Err(err) => println!("Symbolic run failed: {}", err),
+    }
+    if solution.brute_force {
+        println!("Output not affine, searched noun and verb by brute force");
+    }
+    match solution.pair {
+        Some((noun, verb)) => println!("noun: {}, verb: {}", noun, verb),
+        None => println!("Task 2 failed: no noun and verb found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compute;
+
+    #[test]
+    fn example() {
+        let program = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        assert_eq!(Ok(3500), compute(&program, 9, 10));
+    }
+
+    #[test]
+    fn puzzle_input() {
+        assert_eq!(Ok(3409710), compute(&crate::input::PROGRAM_INPUT, 12, 2));
+        assert_eq!(Ok(19690720), compute(&crate::input::PROGRAM_INPUT, 79, 12));
     }
 }
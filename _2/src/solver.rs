@@ -0,0 +1,494 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+
+use crate::compute;
+
+/// Polynomial in noun and verb with integer coefficients.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Polynomial {
+    /// Coefficients by the exponents of noun and verb; zero coefficients are left out
+    terms: BTreeMap<(u32, u32), i128>,
+}
+
+impl Polynomial {
+    pub fn constant(value: i128) -> Polynomial {
+        Polynomial::term((0, 0), value)
+    }
+
+    pub fn noun() -> Polynomial {
+        Polynomial::term((1, 0), 1)
+    }
+
+    pub fn verb() -> Polynomial {
+        Polynomial::term((0, 1), 1)
+    }
+
+    fn term(exponents: (u32, u32), coefficient: i128) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        if coefficient != 0 {
+            terms.insert(exponents, coefficient);
+        }
+        Polynomial { terms }
+    }
+
+    /// The value of the polynomial, if it does not depend on noun or verb.
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((&(0, 0), &value)) if self.terms.len() == 1 => Some(value),
+            Some(_) => None,
+        }
+    }
+
+    /// Coefficients `(a, b, c)` of `a + b*noun + c*verb`, if the polynomial has this form.
+    pub fn affine(&self) -> Option<(i128, i128, i128)> {
+        let coefficient = |exponents| self.terms.get(&exponents).copied().unwrap_or(0);
+        if self.terms.keys().any(|&(noun, verb)| noun + verb > 1) {
+            return None;
+        }
+        Some((
+            coefficient((0, 0)),
+            coefficient((1, 0)),
+            coefficient((0, 1)),
+        ))
+    }
+
+    /// Sum of both polynomials, unless a coefficient overflows.
+    pub fn checked_add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut sum = self.clone();
+        for (&exponents, &coefficient) in &other.terms {
+            sum.add_term(exponents, coefficient)?;
+        }
+        Some(sum)
+    }
+
+    /// Product of both polynomials, unless a coefficient or exponent overflows.
+    pub fn checked_mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial::default();
+        for (&(noun_a, verb_a), &a) in &self.terms {
+            for (&(noun_b, verb_b), &b) in &other.terms {
+                let exponents = (noun_a.checked_add(noun_b)?, verb_a.checked_add(verb_b)?);
+                product.add_term(exponents, a.checked_mul(b)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    fn add_term(&mut self, exponents: (u32, u32), coefficient: i128) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&exponents)
+            .unwrap_or(&0)
+            .checked_add(coefficient)?;
+        if sum == 0 {
+            self.terms.remove(&exponents);
+        } else {
+            self.terms.insert(exponents, sum);
+        }
+        Some(())
+    }
+}
+
+/// Terms ordered by their degree, e.g. `3 + 2*noun - verb + noun*verb^2`.
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        let mut terms: Vec<(&(u32, u32), &i128)> = self.terms.iter().collect();
+        terms.sort_by_key(|&(&(noun, verb), _)| (noun + verb, Reverse(noun)));
+        for (index, (&(noun, verb), &coefficient)) in terms.into_iter().enumerate() {
+            match (index, coefficient < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let mut factors = Vec::new();
+            if coefficient.abs() != 1 || noun + verb == 0 {
+                factors.push(coefficient.unsigned_abs().to_string());
+            }
+            for (name, exponent) in [("noun", noun), ("verb", verb)] {
+                match exponent {
+                    0 => {}
+                    1 => factors.push(name.to_string()),
+                    _ => factors.push(format!("{}^{}", name, exponent)),
+                }
+            }
+            write!(f, "{}", factors.join("*"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason why a program cannot be run symbolically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    /// The program has no room for noun and verb at addresses 1 and 2.
+    TooShort,
+    /// The instruction or one of its addresses depends on noun or verb.
+    Symbolic { i_pointer: usize },
+    /// The instruction is neither an addition, a multiplication nor a halt in position mode.
+    Unsupported { i_pointer: usize, instruction: i128 },
+    /// An address lies outside the program.
+    AddressOutOfRange { i_pointer: usize, address: i128 },
+    /// A coefficient overflows.
+    Overflow { i_pointer: usize },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolicError::TooShort => write!(f, "program too short for noun and verb"),
+            SymbolicError::Symbolic { i_pointer } => {
+                write!(f, "instruction at {} depends on noun or verb", i_pointer)
+            }
+            SymbolicError::Unsupported {
+                i_pointer,
+                instruction,
+            } => write!(
+                f,
+                "unsupported instruction {} at {}",
+                instruction, i_pointer
+            ),
+            SymbolicError::AddressOutOfRange { i_pointer, address } => {
+                write!(f, "address {} out of the program at {}", address, i_pointer)
+            }
+            SymbolicError::Overflow { i_pointer } => write!(f, "overflow at {}", i_pointer),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+/// Run the program with unknown noun and verb, and return the value at address 0 as polynomial.
+///
+/// Only additions, multiplications and halts in position mode are supported. Instructions and the
+/// addresses written to must not depend on noun or verb. An operand read from an address, which
+/// depends on them (e.g. the puzzle inputs start with `1,noun,verb,3`), is unknown, which is fine
+/// as long as it does not end up in the output, an instruction or an address.
+///
+/// The instruction pointer only moves forward over the program, so the run ends after at most a
+/// quarter as many steps as the program is long.
+///
+/// * `program` - gravity assist program
+pub fn symbolic_output(program: &[i32]) -> Result<Polynomial, SymbolicError> {
+    if program.len() < 3 {
+        return Err(SymbolicError::TooShort);
+    }
+    // unknown values are None
+    let mut memory: Vec<Option<Polynomial>> = program
+        .iter()
+        .map(|&value| Some(Polynomial::constant(value as i128)))
+        .collect();
+    memory[1] = Some(Polynomial::noun());
+    memory[2] = Some(Polynomial::verb());
+
+    let mut i_pointer = 0;
+    loop {
+        let out_of_range = |address: i128| SymbolicError::AddressOutOfRange { i_pointer, address };
+        let constant_at = |address: usize| match memory.get(address) {
+            Some(value) => Ok(value.as_ref().and_then(Polynomial::as_constant)),
+            None => Err(out_of_range(address as i128)),
+        };
+        let address_at = |offset: usize| match constant_at(i_pointer + offset)? {
+            Some(address) if address < 0 || address >= memory.len() as i128 => {
+                Err(out_of_range(address))
+            }
+            address => Ok(address.map(|address| address as usize)),
+        };
+        let operand = |offset: usize| -> Result<Option<&Polynomial>, SymbolicError> {
+            Ok(address_at(offset)?.and_then(|address| memory[address].as_ref()))
+        };
+
+        let instruction = constant_at(i_pointer)?.ok_or(SymbolicError::Symbolic { i_pointer })?;
+        let operation = match instruction {
+            1 => Polynomial::checked_add,
+            2 => Polynomial::checked_mul,
+            99 => {
+                return memory
+                    .swap_remove(0)
+                    .ok_or(SymbolicError::Symbolic { i_pointer })
+            }
+            _ => {
+                return Err(SymbolicError::Unsupported {
+                    i_pointer,
+                    instruction,
+                })
+            }
+        };
+        let value = match (operand(1)?, operand(2)?) {
+            (Some(a), Some(b)) => {
+                Some(operation(a, b).ok_or(SymbolicError::Overflow { i_pointer })?)
+            }
+            _ => None,
+        };
+        let target = address_at(3)?.ok_or(SymbolicError::Symbolic { i_pointer })?;
+        memory[target] = value;
+        i_pointer += 4;
+    }
+}
+
+/// Options of `solve`.
+#[derive(Debug, Clone)]
+pub struct SolverOptions {
+    /// Output to search noun and verb for
+    pub target: i32,
+    /// Range of nouns to search
+    pub nouns: Range<i32>,
+    /// Range of verbs to search
+    pub verbs: Range<i32>,
+    /// Number of worker threads of the brute-force search
+    pub threads: usize,
+}
+
+impl Default for SolverOptions {
+    /// Target and ranges of the puzzle, one thread per CPU.
+    fn default() -> SolverOptions {
+        SolverOptions {
+            target: 19690720,
+            nouns: 0..100,
+            verbs: 0..100,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Result of `solve`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Noun and verb giving the target, the smallest noun first and then the smallest verb
+    pub pair: Option<(i32, i32)>,
+    /// Output of the symbolic run, or why the program could not be run symbolically
+    pub expression: Result<Polynomial, SymbolicError>,
+    /// Whether noun and verb were searched by brute force, as the output is not affine, or the
+    /// pair solving the affine output failed to give the target when run
+    pub brute_force: bool,
+}
+
+/// Search noun and verb for the target output.
+///
+/// If the output is an affine function `a + b*noun + c*verb` (as for the puzzle inputs), the
+/// verb follows directly from every noun, and the solution is checked by a single run. Otherwise
+/// all pairs are run by `brute_force`.
+///
+/// * `program` - gravity assist program
+/// * `options` - target, ranges of noun and verb, and threads
+pub fn solve(program: &[i32], options: &SolverOptions) -> Solution {
+    let expression = symbolic_output(program);
+    let affine = expression.as_ref().ok().and_then(Polynomial::affine);
+    let (pair, brute_force) = match affine {
+        Some((a, b, c)) => match solve_affine(a, b, c, options) {
+            // a run still fails, if an intermediate value overflows the word type
+            Some((noun, verb)) if compute(program, noun, verb) != Ok(options.target) => {
+                (brute_force(program, options), true)
+            }
+            pair => (pair, false),
+        },
+        None => (brute_force(program, options), true),
+    };
+    Solution {
+        pair,
+        expression,
+        brute_force,
+    }
+}
+
+/// Solve `a + b*noun + c*verb = target` within the ranges, the smallest noun first.
+fn solve_affine(a: i128, b: i128, c: i128, options: &SolverOptions) -> Option<(i32, i32)> {
+    let target = options.target as i128;
+    options.nouns.clone().find_map(|noun| {
+        // no verb can make up for a term, which does not even fit the polynomial
+        let rest = target
+            .checked_sub(a)?
+            .checked_sub(b.checked_mul(noun as i128)?)?;
+        let verb = match (c, rest) {
+            (0, 0) => options.verbs.start,
+            (0, _) => return None,
+            _ if rest % c != 0 => return None,
+            _ => i32::try_from(rest / c).ok()?,
+        };
+        if options.verbs.contains(&verb) {
+            Some((noun, verb))
+        } else {
+            None
+        }
+    })
+}
+
+/// Run all pairs of noun and verb on several threads, and return the smallest noun with the
+/// smallest verb giving the target.
+///
+/// The threads take turns on the nouns in ascending order, and skip all nouns above the smallest
+/// one solved so far.
+///
+/// * `program` - gravity assist program
+/// * `options` - target, ranges of noun and verb, and threads
+pub fn brute_force(program: &[i32], options: &SolverOptions) -> Option<(i32, i32)> {
+    let threads = options.threads.max(1);
+    let best_noun = AtomicI32::new(options.nouns.end);
+
+    let worker = |first: usize| {
+        for noun in options.nouns.clone().skip(first).step_by(threads) {
+            if noun > best_noun.load(Ordering::Relaxed) {
+                break;
+            }
+            // errors only mean, that the noun and verb are wrong
+            let found = options
+                .verbs
+                .clone()
+                .find(|&verb| compute(program, noun, verb) == Ok(options.target));
+            if let Some(verb) = found {
+                best_noun.fetch_min(noun, Ordering::Relaxed);
+                return Some((noun, verb));
+            }
+        }
+        None
+    };
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|first| scope.spawn(move || worker(first)))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .min()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{brute_force, solve, symbolic_output};
+    use crate::solver::{Polynomial, SolverOptions, SymbolicError};
+
+    // [3] = [noun] + [verb], which is never used, then [0] = noun * verb
+    const NOUN_TIMES_VERB: [i32; 9] = [1, 0, 0, 3, 2, 1, 2, 0, 99];
+
+    #[test]
+    fn polynomial() {
+        let noun = Polynomial::noun();
+        let verb = Polynomial::verb();
+        let p = Polynomial::constant(3)
+            .checked_add(&noun.checked_mul(&Polynomial::constant(2)).unwrap())
+            .unwrap()
+            .checked_add(&verb.checked_mul(&Polynomial::constant(-1)).unwrap())
+            .unwrap();
+        assert_eq!("3 + 2*noun - verb", p.to_string());
+        assert_eq!(Some((3, 2, -1)), p.affine());
+
+        let square = p.checked_mul(&verb).unwrap().checked_mul(&verb).unwrap();
+        assert_eq!("3*verb^2 + 2*noun*verb^2 - verb^3", square.to_string());
+        assert_eq!(None, square.affine());
+        assert_eq!(None, square.as_constant());
+        assert_eq!(
+            "0",
+            p.checked_add(&p.checked_mul(&Polynomial::constant(-1)).unwrap())
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn symbolic() {
+        // [0] = ([noun] + [verb]) * [11]
+        let program = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        assert_eq!(
+            Err(SymbolicError::Symbolic { i_pointer: 8 }),
+            symbolic_output(&program)
+        );
+
+        let program = NOUN_TIMES_VERB;
+        assert_eq!("noun*verb", symbolic_output(&program).unwrap().to_string());
+
+        let program = [1, 1, 2, 0, 7];
+        assert_eq!(
+            Err(SymbolicError::Unsupported {
+                i_pointer: 4,
+                instruction: 7
+            }),
+            symbolic_output(&program)
+        );
+    }
+
+    #[test]
+    fn puzzle_input() {
+        let expression = symbolic_output(&crate::input::PROGRAM_INPUT).unwrap();
+        let (a, b, c) = expression.affine().unwrap();
+        assert_eq!(3409710, a + b * 12 + c * 2);
+
+        let solution = solve(&crate::input::PROGRAM_INPUT, &SolverOptions::default());
+        assert_eq!(Some((79, 12)), solution.pair);
+        assert!(!solution.brute_force);
+    }
+
+    #[test]
+    fn fallback() {
+        let options = SolverOptions {
+            target: 12,
+            ..SolverOptions::default()
+        };
+        let solution = solve(&NOUN_TIMES_VERB, &options);
+        assert_eq!(Some((1, 12)), solution.pair);
+        assert!(solution.brute_force);
+
+        // [0] = [noun] + [verb], where [4] = 99
+        let options = SolverOptions {
+            target: 100,
+            ..SolverOptions::default()
+        };
+        let solution = solve(&[1, 0, 0, 0, 99], &options);
+        assert_eq!(Some((0, 4)), solution.pair);
+        assert!(solution.expression.is_err());
+
+        let options = SolverOptions {
+            target: 1_000_000,
+            ..SolverOptions::default()
+        };
+        assert_eq!(None, solve(&NOUN_TIMES_VERB, &options).pair);
+    }
+
+    #[test]
+    fn huge_coefficients() {
+        // [0] = (2^31 - 1)^4 * noun, which overflows any run but noun 0
+        let program = [
+            1, 0, 0, 3, 2, 20, 20, 21, 2, 21, 21, 21, 2, 21, 1, 0, 99, 0, 0, 0, 2147483647, 0,
+        ];
+        let (_, b, _) = symbolic_output(&program).unwrap().affine().unwrap();
+        assert!(b.checked_mul(99).is_none());
+
+        let solution = solve(&program, &SolverOptions::default());
+        assert_eq!(None, solution.pair);
+        assert!(!solution.brute_force);
+
+        // the affine pair (0, 0) overflows when run, so all pairs are tried
+        let options = SolverOptions {
+            target: 0,
+            ..SolverOptions::default()
+        };
+        let solution = solve(&program, &options);
+        assert_eq!(None, solution.pair);
+        assert!(solution.brute_force);
+    }
+
+    #[test]
+    fn brute_force_threads() {
+        // all threads find pairs, but the smallest noun wins
+        let program = NOUN_TIMES_VERB;
+        for threads in 1..6 {
+            let options = SolverOptions {
+                target: 24,
+                nouns: 2..30,
+                verbs: 1..20,
+                threads,
+            };
+            assert_eq!(Some((2, 12)), brute_force(&program, &options));
+        }
+    }
+}
@@ -1,88 +1,42 @@
 mod input;
 
-fn fetch_input_a(instr: &[i32], i_pointer: usize) -> i32 {
-    if instr[i_pointer] % 1000 / 100 == 1 {
-        // use int division
-        instr[i_pointer + 1] // immediate mode
-    } else {
-        instr[instr[i_pointer + 1] as usize] // position mode
-    }
-}
-
-fn fetch_input_b(instr: &[i32], i_pointer: usize) -> i32 {
-    if instr[i_pointer] % 10000 / 1000 == 1 {
-        // use int division
-        instr[i_pointer + 2] // immediate mode
-    } else {
-        instr[instr[i_pointer + 2] as usize] // position mode
-    }
-}
+use std::env;
+use std::process;
 
-fn fetch_input(instr: &[i32], i_pointer: usize) -> (i32, i32, usize) {
-    let a = fetch_input_a(&instr, i_pointer);
-    let b = fetch_input_b(&instr, i_pointer);
-    let target = instr[i_pointer + 3] as usize;
+use intcode::intcode_cmp::{IntCodeComputer, RunState};
+use intcode::intcode_error::IntcodeError;
+use intcode::intcode_io::{FnOutput, LineInput};
+use intcode::intcode_parse::load_program;
 
-    (a, b, target)
+/// Run the diagnostic program, asking for input on stdin and printing its output.
+///
+/// * `program` - diagnostic program
+fn compute(program: &[i32]) -> Result<RunState<i32>, IntcodeError> {
+    let output = FnOutput(|value: i32| println!("Output: {}", value));
+    let mut icc = IntCodeComputer::with_io(program, LineInput::stdin(), output);
+    icc.compute()
 }
 
-fn compute(instr: &mut [i32]) {
-    let mut i_pointer = 0;
-
-    while instr[i_pointer] != 99 {
-        if instr[i_pointer] % 10 == 1 {
-            let (a, b, target) = fetch_input(&instr, i_pointer);
-            instr[target] = a + b;
-            i_pointer += 4;
-        } else if instr[i_pointer] % 10 == 2 {
-            let (a, b, target) = fetch_input(&instr, i_pointer);
-            instr[target] = a * b;
-            i_pointer += 4;
-        } else if instr[i_pointer] == 3 {
-            println!("Please enter your input:");
-            let mut input_str = String::new();
-            std::io::stdin().read_line(&mut input_str).unwrap();
-            input_str = input_str.replace("\n", "");
-            let input_int: i32 = input_str.parse().unwrap();
-            let target = instr[i_pointer + 1] as usize;
-            instr[target] = input_int;
-            i_pointer += 2;
-        } else if instr[i_pointer] % 10 == 4 {
-            let a = fetch_input_a(&instr, i_pointer);
-            println!("Output (at {}): {}", i_pointer, a);
-            i_pointer += 2;
-        } else if instr[i_pointer] % 10 == 5 {
-            let a = fetch_input_a(&instr, i_pointer);
-            let b = fetch_input_b(&instr, i_pointer) as usize;
-            if a != 0 {
-                i_pointer = b;
-            } else {
-                i_pointer += 3;
-            }
-        } else if instr[i_pointer] % 10 == 6 {
-            let a = fetch_input_a(&instr, i_pointer);
-            let b = fetch_input_b(&instr, i_pointer) as usize;
-            if a == 0 {
-                i_pointer = b;
-            } else {
-                i_pointer += 3;
-            }
-        } else if instr[i_pointer] % 10 == 7 {
-            let (a, b, target) = fetch_input(&instr, i_pointer);
-            instr[target] = (a < b) as i32;
-            i_pointer += 4;
-        } else if instr[i_pointer] % 10 == 8 {
-            let (a, b, target) = fetch_input(&instr, i_pointer);
-            instr[target] = (a == b) as i32;
-            i_pointer += 4;
-        } else {
-            println!("WRONG INSTRUCTION AT {}: {}", i_pointer, instr[i_pointer]);
-            break;
+/// Usage: advent-of-code-2019-5 [program-file]
+///
+/// Without a program file, the puzzle input is used. The program cannot be read from stdin (`-`),
+/// as the input values of the diagnostic program are.
+fn main() {
+    let program: Vec<i32> = match env::args().nth(1) {
+        Some(path) if path == "-" => {
+            eprintln!("Cannot load the program from stdin, which provides its input values");
+            process::exit(2);
         }
-    }
-}
+        Some(path) => load_program(&path).unwrap_or_else(|err| {
+            eprintln!("Cannot load {}: {}", path, err);
+            process::exit(1);
+        }),
+        None => input::PROGRAM_INPUT.to_vec(),
+    };
 
-fn main() {
     println!("Task 1:");
-    compute(&mut input::PROGRAM_INPUT);
+    if let Err(err) = compute(&program) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
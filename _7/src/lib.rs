@@ -0,0 +1,3 @@
+//! Search of the phase settings of the amplifiers, used by the puzzle solution in this crate.
+
+pub mod phase_search;
@@ -1,76 +1,137 @@
 mod input;
-mod intcode_cmp;
 
-use intcode_cmp::IntCodeComputer;
+use std::env;
+use std::process;
+use std::sync::Mutex;
 
-fn calc_single_sequence(program: &[i32], sequence: &[i32]) -> i32 {
-    let mut input_signal = 0;
-    for phase_setting in sequence {
-        let mut icc = IntCodeComputer::new(&program.to_vec());
-        icc.input.send(*phase_setting).unwrap();
-        icc.input.send(input_signal).unwrap();
-        icc.compute();
-        input_signal = icc.output.recv().unwrap();
-    }
-    input_signal
-}
+use advent_of_code_2019_7::phase_search::{search_permutations, SearchOptions};
+use intcode::intcode_circuit::{Circuit, CircuitError};
+use intcode::intcode_parse::load_program;
 
-fn calc_single_sequence_feedback(program: &[i32], sequence: &[i32]) -> i32 {
-    // initialize computers
-    let mut iccs: Vec<IntCodeComputer> = sequence
-        .iter()
-        .map(|init_code| {
-            let mut icc = IntCodeComputer::new(&program.to_vec());
-            icc.set_return_control(true);
-            icc.input.send(*init_code).unwrap();
-            icc
-        })
+/// Run a chain of amplifiers, one per phase setting, and return the final signal of the last one.
+///
+/// * `program` - amplifier controller software
+/// * `sequence` - phase settings of the amplifiers
+/// * `feedback` - connect the last amplifier back to the first one
+///
+/// Without amplifiers, the initial signal 0 passes through unchanged. Fails if the amplifiers
+/// wait for each other, loop forever, or the last one halts without producing a signal.
+fn calc_amplifier_chain(
+    program: &[i32],
+    sequence: &[i32],
+    feedback: bool,
+) -> Result<i32, CircuitError> {
+    let names: Vec<String> = (0..sequence.len())
+        .map(|index| format!("amp{}", index))
         .collect();
+    let last = match names.last() {
+        Some(last) => last,
+        None => return Ok(0),
+    };
 
-    // run the program with feedback loop
-    let mut input_signal = 0;
-    loop {
-        for icc in &mut iccs {
-            icc.input.send(input_signal).unwrap();
-            icc.compute();
-            input_signal = icc.output.try_recv().unwrap();
-        }
-        if iccs[4].finished() {
-            break;
-        }
+    let mut builder = Circuit::builder();
+    builder.loop_detection(true);
+    for (index, phase_setting) in sequence.iter().enumerate() {
+        // the first amplifier also gets the initial signal 0
+        let inputs = if index == 0 {
+            vec![*phase_setting, 0]
+        } else {
+            vec![*phase_setting]
+        };
+        builder.node(&names[index], program, &inputs);
+    }
+    for pair in names.windows(2) {
+        builder.connect(&pair[0], &pair[1]);
     }
-    input_signal
+    if feedback {
+        builder.connect(last, &names[0]);
+    }
+
+    let mut circuit = builder.build()?;
+    circuit.run()?;
+    circuit
+        .last_output(last)
+        .ok_or_else(|| CircuitError::NoSignal { node: last.clone() })
 }
 
-fn calc_max_signal<F>(program: &[i32], phases: &mut [i32], func: F) -> i32
+fn calc_single_sequence(program: &[i32], sequence: &[i32]) -> Result<i32, CircuitError> {
+    calc_amplifier_chain(program, sequence, false)
+}
+
+fn calc_single_sequence_feedback(program: &[i32], sequence: &[i32]) -> Result<i32, CircuitError> {
+    calc_amplifier_chain(program, sequence, true)
+}
+
+/// Find the phase settings with the highest signal, searching all permutations in parallel.
+///
+/// Returns the signal and the phase settings, or the error of the smallest failing sequence.
+/// Panics if there are more phases than `search_permutations` can permute.
+fn calc_max_signal<F>(
+    program: &[i32],
+    phases: &[i32],
+    func: F,
+) -> Result<(i32, Vec<i32>), CircuitError>
 where
-    F: Fn(&[i32], &[i32]) -> i32,
+    F: Fn(&[i32], &[i32]) -> Result<i32, CircuitError> + Sync,
 {
-    let mut output_signals = Vec::new();
-    permutohedron::heap_recursive(phases, |permutation| {
-        output_signals.push(func(program, &permutation.to_vec()))
-    });
-    *output_signals.iter().max().unwrap()
+    // after a failure, only smaller sequences are run, so the threads agree on the error
+    let error: Mutex<Option<(Vec<i32>, CircuitError)>> = Mutex::new(None);
+    let after_failure = |error: &Option<(Vec<i32>, CircuitError)>, sequence: &[i32]| match error {
+        Some((failed, _)) => sequence > &failed[..],
+        None => false,
+    };
+    let result = search_permutations(phases, &SearchOptions::default(), |sequence| {
+        if after_failure(&error.lock().unwrap(), sequence) {
+            return i32::MIN;
+        }
+        func(program, sequence).unwrap_or_else(|err| {
+            let mut error = error.lock().unwrap();
+            if !after_failure(&error, sequence) {
+                *error = Some((sequence.to_vec(), err));
+            }
+            i32::MIN
+        })
+    })
+    .expect("TOO MANY PHASES TO PERMUTE");
+
+    if let Some((_, err)) = error.into_inner().unwrap() {
+        return Err(err);
+    }
+    let best = result.best().unwrap();
+    Ok((best.signal, best.sequence.clone()))
 }
 
+/// Usage: advent-of-code-2019-7 [program-file]
+///
+/// Without a program file (`-` for stdin), the puzzle input is used.
 fn main() {
-    let result = calc_max_signal(
-        &input::PROGRAM_INPUT,
-        &mut [0, 1, 2, 3, 4],
-        calc_single_sequence,
+    let program: Vec<i32> = match env::args().nth(1) {
+        Some(path) => load_program(&path).unwrap_or_else(|err| {
+            eprintln!("Cannot load {}: {}", path, err);
+            process::exit(1);
+        }),
+        None => input::PROGRAM_INPUT.to_vec(),
+    };
+
+    let (result, sequence) =
+        calc_max_signal(&program, &[0, 1, 2, 3, 4], calc_single_sequence).unwrap();
+    println!(
+        "Result for task 1: {:?} (phase settings {:?})",
+        result, sequence
     );
-    println!("Result for task 1: {:?}", result);
 
-    let result = calc_max_signal(
-        &input::PROGRAM_INPUT,
-        &mut [9, 8, 7, 6, 5],
-        calc_single_sequence_feedback,
+    let (result, sequence) =
+        calc_max_signal(&program, &[9, 8, 7, 6, 5], calc_single_sequence_feedback).unwrap();
+    println!(
+        "Result for task 2: {:?} (phase settings {:?})",
+        result, sequence
     );
-    println!("Result for task 2: {:?}", result);
 }
 
 #[cfg(test)]
 mod tests {
+    use intcode::intcode_circuit::{BlockedNode, CircuitError};
+
     use crate::calc_max_signal;
     use crate::calc_single_sequence;
     use crate::calc_single_sequence_feedback;
@@ -83,7 +144,7 @@ mod tests {
             ],
             &[4, 3, 2, 1, 0],
         );
-        assert_eq!(43210, result);
+        assert_eq!(Ok(43210), result);
     }
 
     #[test]
@@ -95,7 +156,7 @@ mod tests {
             ],
             &[0, 1, 2, 3, 4],
         );
-        assert_eq!(54321, result);
+        assert_eq!(Ok(54321), result);
     }
 
     #[test]
@@ -107,7 +168,7 @@ mod tests {
             ],
             &[1, 0, 4, 3, 2],
         );
-        assert_eq!(65210, result);
+        assert_eq!(Ok(65210), result);
     }
 
     #[test]
@@ -116,10 +177,10 @@ mod tests {
             &[
                 3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
             ],
-            &mut [0, 1, 2, 3, 4],
+            &[0, 1, 2, 3, 4],
             calc_single_sequence,
         );
-        assert_eq!(43210, result);
+        assert_eq!(Ok((43210, vec![4, 3, 2, 1, 0])), result);
     }
 
     #[test]
@@ -129,10 +190,10 @@ mod tests {
                 3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4,
                 23, 99, 0, 0,
             ],
-            &mut [0, 1, 2, 3, 4],
+            &[0, 1, 2, 3, 4],
             calc_single_sequence,
         );
-        assert_eq!(54321, result);
+        assert_eq!(Ok((54321, vec![0, 1, 2, 3, 4])), result);
     }
 
     #[test]
@@ -142,10 +203,10 @@ mod tests {
                 3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7, 33,
                 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
             ],
-            &mut [0, 1, 2, 3, 4],
+            &[0, 1, 2, 3, 4],
             calc_single_sequence,
         );
-        assert_eq!(65210, result);
+        assert_eq!(Ok((65210, vec![1, 0, 4, 3, 2])), result);
     }
 
     #[test]
@@ -157,7 +218,7 @@ mod tests {
             ],
             &[9, 8, 7, 6, 5],
         );
-        assert_eq!(139629729, result)
+        assert_eq!(Ok(139629729), result)
     }
 
     #[test]
@@ -170,7 +231,7 @@ mod tests {
             ],
             &[9, 7, 8, 5, 6],
         );
-        assert_eq!(18216, result)
+        assert_eq!(Ok(18216), result)
     }
 
     #[test]
@@ -180,10 +241,10 @@ mod tests {
                 3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28,
                 -1, 28, 1005, 28, 6, 99, 0, 0, 5,
             ],
-            &mut [9, 8, 7, 6, 5],
-            calc_single_sequence_feedback
+            &[9, 8, 7, 6, 5],
+            calc_single_sequence_feedback,
         );
-        assert_eq!(139629729, result)
+        assert_eq!(Ok((139629729, vec![9, 8, 7, 6, 5])), result)
     }
 
     #[test]
@@ -194,9 +255,75 @@ mod tests {
                 54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53,
                 55, 53, 4, 53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
             ],
-            &mut [9, 7, 8, 5, 6],
-            calc_single_sequence_feedback
+            &[9, 7, 8, 5, 6],
+            calc_single_sequence_feedback,
         );
-        assert_eq!(18216, result)
+        assert_eq!(Ok((18216, vec![9, 7, 8, 5, 6])), result)
+    }
+
+    #[test]
+    fn sequence_feedback_deadlock() {
+        // every amplifier reads two signals before writing one
+        let program = [3, 13, 3, 14, 3, 14, 4, 14, 1105, 1, 4, 99, 99, 0, 0];
+        let blocked: Vec<BlockedNode> = ["amp0", "amp1", "amp2", "amp3", "amp4"]
+            .iter()
+            .map(|name| BlockedNode {
+                name: name.to_string(),
+                i_pointer: if *name == "amp0" { 4 } else { 2 },
+                queued: 0,
+            })
+            .collect();
+        let expected = Err(CircuitError::Deadlock(blocked));
+
+        let result = calc_single_sequence_feedback(&program, &[9, 8, 7, 6, 5]);
+        assert_eq!(expected, result);
+        let result = calc_max_signal(&program, &[9, 8, 7, 6, 5], calc_single_sequence_feedback);
+        assert_eq!(expected, result.map(|_| 0));
+    }
+
+    #[test]
+    fn first_failing_sequence() {
+        // amplifiers with a phase setting of 2 or more run into an invalid instruction
+        let program = [
+            3, 20, 3, 21, 4, 21, 1007, 20, 2, 22, 1005, 22, 14, 0, 99, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let expected = calc_single_sequence(&program, &[0, 1, 2, 3, 4]);
+        assert!(expected.is_err());
+        for _ in 0..20 {
+            let result = calc_max_signal(&program, &[4, 3, 2, 1, 0], calc_single_sequence);
+            assert_eq!(expected, result.map(|(signal, _)| signal));
+        }
+    }
+
+    #[test]
+    fn sequence_without_signal() {
+        // the amplifiers read their phase setting and halt
+        let result = calc_single_sequence(&[3, 0, 99], &[0, 1, 2, 3, 4]);
+        assert_eq!(
+            Err(CircuitError::NoSignal {
+                node: "amp4".to_string()
+            }),
+            result
+        );
+        let result = calc_single_sequence_feedback(&[3, 0, 99], &[9, 8, 7, 6, 5]);
+        assert_eq!(
+            Err(CircuitError::NoSignal {
+                node: "amp4".to_string()
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn sequence_long() {
+        // every amplifier adds its phase setting to the signal
+        let program = [3, 11, 3, 12, 1, 11, 12, 12, 4, 12, 99, 0, 0];
+        assert_eq!(Ok(300), calc_single_sequence(&program, &[1; 300]));
+    }
+
+    #[test]
+    fn sequence_empty() {
+        assert_eq!(Ok(0), calc_single_sequence(&[3, 0, 99], &[]));
+        assert_eq!(Ok(0), calc_single_sequence_feedback(&[3, 0, 99], &[]));
     }
 }
@@ -0,0 +1,297 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Most phases `search_permutations` permutes, as 21! does not fit into `u64`.
+pub const MAX_PHASES: usize = 20;
+
+/// Options of `search_permutations`.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Number of worker threads
+    pub threads: usize,
+    /// Number of best sequences to report
+    pub top: usize,
+    /// Flag to stop the search early, e.g. from another thread or from the evaluation itself
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for SearchOptions {
+    /// One thread per CPU, report the best sequence only, no cancellation.
+    fn default() -> SearchOptions {
+        SearchOptions {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            top: 1,
+            cancel: None,
+        }
+    }
+}
+
+/// Sequence with its signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranked<T, S> {
+    pub signal: S,
+    pub sequence: Vec<T>,
+}
+
+/// Result of `search_permutations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult<T, S> {
+    /// Best sequences, highest signal first. Sequences with equal signals are ordered as in the
+    /// lexicographic order of the permutations, i.e. by the positions of their phases in the
+    /// input.
+    pub top: Vec<Ranked<T, S>>,
+    /// Number of sequences evaluated
+    pub evaluated: u64,
+    /// Whether the search was cancelled before all sequences were evaluated
+    pub cancelled: bool,
+}
+
+impl<T, S> SearchResult<T, S> {
+    /// The sequence with the highest signal, unless the search was cancelled before any.
+    pub fn best(&self) -> Option<&Ranked<T, S>> {
+        self.top.first()
+    }
+}
+
+/// Error of `search_permutations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchError {
+    /// More than `MAX_PHASES` phases are given.
+    TooManyPhases(usize),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::TooManyPhases(phases) => write!(
+                f,
+                "cannot permute {} phases, at most {} are supported",
+                phases, MAX_PHASES
+            ),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+/// `n!`, which fits into `u64` for `n` up to `MAX_PHASES`.
+fn factorial(n: usize) -> u64 {
+    (1..=n as u64).product()
+}
+
+/// The `rank`-th permutation of `0..n` in lexicographic order.
+fn nth_permutation(n: usize, mut rank: u64) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut permutation = Vec::with_capacity(n);
+    for position in 0..n {
+        let block = factorial(n - position - 1);
+        permutation.push(remaining.remove((rank / block) as usize));
+        rank %= block;
+    }
+    permutation
+}
+
+/// Advance to the next permutation in lexicographic order. Returns false after the last one.
+fn next_permutation(permutation: &mut [usize]) -> bool {
+    let pivot = match (1..permutation.len())
+        .rev()
+        .find(|&i| permutation[i - 1] < permutation[i])
+    {
+        Some(i) => i - 1,
+        None => return false,
+    };
+    let successor = (pivot + 1..permutation.len())
+        .rev()
+        .find(|&i| permutation[i] > permutation[pivot])
+        .unwrap();
+    permutation.swap(pivot, successor);
+    permutation[pivot + 1..].reverse();
+    true
+}
+
+/// Search the permutation of the phases with the highest signal, on several threads.
+///
+/// The permutations are numbered in lexicographic order, and every thread evaluates a contiguous
+/// range of numbers. Each thread starts right at its range, so the partitioning costs nothing,
+/// no matter how many phases there are. Fails if there are more than `MAX_PHASES`.
+///
+/// * `phases` - phase settings to permute; equal settings are permuted as if they were different
+/// * `options` - number of threads and reported sequences, and cancellation
+/// * `func` - evaluation of a sequence, e.g. running an amplifier chain
+///
+/// ```
+/// use advent_of_code_2019_7::phase_search::{search_permutations, SearchOptions};
+///
+/// let options = SearchOptions { top: 2, ..SearchOptions::default() };
+/// let result =
+///     search_permutations(&[1, 2, 3], &options, |s| 100 * s[0] + 10 * s[1] + s[2]).unwrap();
+///
+/// assert_eq!(321, result.top[0].signal);
+/// assert_eq!(vec![3, 2, 1], result.top[0].sequence);
+/// assert_eq!(312, result.top[1].signal);
+/// assert_eq!(6, result.evaluated);
+/// ```
+pub fn search_permutations<T, S, F>(
+    phases: &[T],
+    options: &SearchOptions,
+    func: F,
+) -> Result<SearchResult<T, S>, SearchError>
+where
+    T: Copy + Sync,
+    S: Ord + Copy + Send,
+    F: Fn(&[T]) -> S + Sync,
+{
+    if phases.len() > MAX_PHASES {
+        return Err(SearchError::TooManyPhases(phases.len()));
+    }
+    let total = factorial(phases.len());
+    let threads = options.threads.max(1) as u64;
+    let chunk = total.div_ceil(threads);
+    let top = options.top.max(1);
+    let cancelled = || {
+        options
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    };
+
+    // every worker keeps its best sequences in a heap with the worst on top
+    let worker = |start: u64, end: u64| {
+        let mut best = BinaryHeap::new();
+        let mut evaluated = 0;
+        let mut permutation = nth_permutation(phases.len(), start);
+        let mut sequence = Vec::with_capacity(phases.len());
+        for rank in start..end {
+            if cancelled() {
+                break;
+            }
+            sequence.clear();
+            sequence.extend(permutation.iter().map(|&index| phases[index]));
+            best.push(Reverse((func(&sequence), Reverse(rank))));
+            if best.len() > top {
+                best.pop();
+            }
+            evaluated += 1;
+            next_permutation(&mut permutation);
+        }
+        (best.into_vec(), evaluated)
+    };
+
+    let results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|number| (number * chunk, ((number + 1) * chunk).min(total)))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| scope.spawn(move || worker(start, end)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let evaluated = results.iter().map(|(_, evaluated)| evaluated).sum();
+    let mut best: Vec<(S, u64)> = results
+        .into_iter()
+        .flat_map(|(best, _)| best)
+        .map(|Reverse((signal, Reverse(rank)))| (signal, rank))
+        .collect();
+    best.sort_by_key(|&(signal, rank)| (Reverse(signal), rank));
+    best.truncate(top);
+
+    Ok(SearchResult {
+        top: best
+            .into_iter()
+            .map(|(signal, rank)| Ranked {
+                signal,
+                sequence: nth_permutation(phases.len(), rank)
+                    .into_iter()
+                    .map(|index| phases[index])
+                    .collect(),
+            })
+            .collect(),
+        evaluated,
+        cancelled: evaluated < total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use crate::phase_search::{next_permutation, nth_permutation, search_permutations};
+    use crate::phase_search::{Ranked, SearchError, SearchOptions};
+
+    #[test]
+    fn permutation_order() {
+        let mut permutation = nth_permutation(4, 0);
+        for rank in 1..24 {
+            assert!(next_permutation(&mut permutation));
+            assert_eq!(nth_permutation(4, rank), permutation);
+        }
+        assert_eq!(vec![3, 2, 1, 0], permutation);
+        assert!(!next_permutation(&mut permutation));
+    }
+
+    #[test]
+    fn all_threads_agree() {
+        // the signal has many ties, which must be resolved the same way on any number of threads
+        let phases = [5, 6, 7, 8, 9, 10, 11];
+        let func = |s: &[i32]| s[0] * s[6] - s[3];
+        let results: Vec<_> = [1, 2, 3, 8, 100]
+            .iter()
+            .map(|&threads| {
+                let options = SearchOptions {
+                    threads,
+                    top: 5,
+                    cancel: None,
+                };
+                search_permutations(&phases, &options, func).unwrap()
+            })
+            .collect();
+
+        assert_eq!(5040, results[0].evaluated);
+        assert!(!results[0].cancelled);
+        assert_eq!(
+            Some(&Ranked {
+                signal: 105,
+                sequence: vec![10, 6, 7, 5, 8, 9, 11]
+            }),
+            results[0].best()
+        );
+        assert!(results.iter().all(|result| *result == results[0]));
+    }
+
+    #[test]
+    fn cancellation() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let count = AtomicU64::new(0);
+        let options = SearchOptions {
+            threads: 1,
+            top: 1,
+            cancel: Some(cancel.clone()),
+        };
+        let result = search_permutations(&[1, 2, 3, 4, 5], &options, |s| {
+            if count.fetch_add(1, Ordering::Relaxed) == 9 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            s[4]
+        })
+        .unwrap();
+        assert_eq!(10, result.evaluated);
+        assert!(result.cancelled);
+        assert_eq!(5, result.best().unwrap().signal);
+    }
+
+    #[test]
+    fn too_many_phases() {
+        let phases: Vec<i32> = (0..21).collect();
+        let result = search_permutations(&phases, &SearchOptions::default(), |s| s[0]);
+        assert_eq!(Err(SearchError::TooManyPhases(21)), result);
+    }
+}
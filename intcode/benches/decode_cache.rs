@@ -0,0 +1,75 @@
+/// Benchmark of the decode cache against decoding every instruction on execution.
+///
+/// Usage: cargo bench
+///
+/// Prints the time per run and per instruction of both engines for a long-running loop and for a
+/// sweep of many short runs, like the noun and verb search of day 2.
+use std::time::{Duration, Instant};
+
+use intcode::intcode_asm::assemble;
+use intcode::intcode_cmp::IntCodeComputer;
+
+// sum up the products of all pairs of numbers below [limit]
+const NESTED_LOOPS: &str = "
+        in [limit]
+outer:  add [i], #1, [i]
+        eq [i], [limit], [flag]
+        jnz [flag], #done
+        add #0, #0, [j]
+inner:  mul [i], [j], [product]
+        add [sum], [product], [sum]
+        add [j], #1, [j]
+        lt [j], [i], [flag]
+        jnz [flag], #inner
+        jz #0, #outer
+done:   out [sum]
+        hlt
+limit:  data 0
+i:      data 0
+j:      data 0
+flag:   data 0
+product: data 0
+sum:    data 0
+";
+
+/// Run the program with the input, and return its output and the number of executed instructions.
+fn run(program: &[i64], input: i64, cache: bool) -> (i64, u64) {
+    let mut icc = IntCodeComputer::new(program);
+    icc.set_decode_cache(cache);
+    icc.input.push_back(input);
+    icc.compute().unwrap();
+    (icc.output.pop_front().unwrap(), icc.steps())
+}
+
+/// Time a workload on both engines, after checking that they agree.
+fn compare<F: Fn(bool) -> (i64, u64)>(name: &str, workload: F) {
+    assert_eq!(workload(false), workload(true));
+    for &cache in &[false, true] {
+        let mut runs = 0;
+        let mut steps = 0;
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(1) {
+            steps += workload(cache).1;
+            runs += 1;
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "{:<14} {:<10} {:>10.3} ms/run {:>8.2} ns/instruction",
+            name,
+            if cache { "cached" } else { "plain" },
+            elapsed.as_secs_f64() * 1e3 / runs as f64,
+            elapsed.as_secs_f64() * 1e9 / steps as f64,
+        );
+    }
+}
+
+fn main() {
+    let program = assemble(NESTED_LOOPS).unwrap();
+    compare("nested loops", |cache| run(&program, 1000, cache));
+    compare("sweep", |cache| {
+        (0..10_000).fold((0, 0), |(sum, steps), input| {
+            let (output, more) = run(&program, input % 10 + 1, cache);
+            (sum + output, steps + more)
+        })
+    });
+}
@@ -0,0 +1,121 @@
+//! Runner for text-based Intcode programs, which read and write ASCII.
+//!
+//! Usage: `intcode-ascii <program-file> [--script FILE] [--transcript FILE]`
+//!
+//! Without a script, commands are read from the terminal until the program halts or the input
+//! ends. With a script, its lines are sent as commands one after another. Values outside of the
+//! ASCII range are printed as results. The transcript records the whole dialog, with commands
+//! marked by `> `.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process;
+
+use intcode::intcode_ascii::{AsciiComputer, AsciiOutput};
+use intcode::intcode_cmp::RunState;
+use intcode::intcode_parse::load_program;
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} <program-file> [--script FILE] [--transcript FILE]",
+        program
+    );
+    process::exit(2);
+}
+
+fn exit_on_error<T, E: std::fmt::Display>(result: Result<T, E>, context: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}: {}", context, err);
+        process::exit(1);
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut program_file = None;
+    let mut script_file = None;
+    let mut transcript_file = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--script" => script_file = Some(rest.next().unwrap_or_else(|| usage(&args[0]))),
+            "--transcript" => {
+                transcript_file = Some(rest.next().unwrap_or_else(|| usage(&args[0])))
+            }
+            _ if program_file.is_none() => program_file = Some(arg),
+            _ => usage(&args[0]),
+        }
+    }
+    let program_file = program_file.unwrap_or_else(|| usage(&args[0]));
+
+    let program: Vec<i64> = exit_on_error(
+        load_program(program_file),
+        &format!("Cannot load {}", program_file),
+    );
+
+    let mut script = script_file.map(|file| {
+        let text = exit_on_error(fs::read_to_string(file), &format!("Cannot read {}", file));
+        text.lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+            .into_iter()
+    });
+    let mut transcript = transcript_file.map(|file| {
+        let file = exit_on_error(File::create(file), &format!("Cannot create {}", file));
+        BufWriter::new(file)
+    });
+    let mut record = |line: &str| {
+        if let Some(transcript) = transcript.as_mut() {
+            exit_on_error(writeln!(transcript, "{}", line), "Cannot write transcript");
+        }
+    };
+
+    let mut ascii = AsciiComputer::new(&program);
+    let stdin = io::stdin();
+    loop {
+        let (state, output) = exit_on_error(ascii.run(), "Program failed");
+        for item in output {
+            let line = match item {
+                AsciiOutput::Line(line) => line,
+                AsciiOutput::Value(value) => format!("Result: {}", value),
+            };
+            println!("{}", line);
+            record(&line);
+        }
+        if state == RunState::Halted {
+            break;
+        }
+
+        let command = match script.as_mut() {
+            Some(script) => match script.next() {
+                Some(command) => {
+                    println!("> {}", command);
+                    command
+                }
+                None => {
+                    eprintln!("Script ended while the program awaits input");
+                    break;
+                }
+            },
+            None => {
+                print!("> ");
+                io::stdout().flush().unwrap();
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line).unwrap() == 0 {
+                    println!();
+                    break;
+                }
+                line.trim_end_matches(&['\r', '\n'][..]).to_string()
+            }
+        };
+        record(&format!("> {}", command));
+        if let Err(err) = ascii.send(&command) {
+            eprintln!("{}", err);
+        }
+    }
+
+    if let Some(mut transcript) = transcript {
+        exit_on_error(transcript.flush(), "Cannot write transcript");
+    }
+}
@@ -0,0 +1,23 @@
+//! Control-flow graph of Intcode programs in the DOT language of Graphviz.
+//!
+//! Usage: `intcode-cfg <program-file> [entry-address...]`
+//!
+//! The program file holds the comma-separated program, as given by the puzzles (`-` for stdin).
+//! Additional entry addresses make code behind computed jumps visible. Render the graph e.g. with
+//! `intcode-cfg program.txt | dot -Tsvg > program.svg`.
+
+use std::env;
+use std::process;
+
+use intcode::intcode_cfg::control_flow_graph;
+use intcode::intcode_parse::load_program_and_entries;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (program, entries) = load_program_and_entries::<i64>(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    });
+
+    print!("{}", control_flow_graph(&program, &entries).to_dot());
+}
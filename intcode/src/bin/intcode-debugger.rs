@@ -0,0 +1,224 @@
+//! Interactive debugger for Intcode programs.
+//!
+//! Usage: `intcode-debugger <program-file>`
+//!
+//! The program file holds the comma-separated program, as given by the puzzles (`-` for stdin).
+//! Type `help` at the prompt for the list of commands.
+
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::str::FromStr;
+
+use intcode::intcode_cmp::IntCodeComputer;
+use intcode::intcode_debug::{Debugger, OutputCondition, Stop};
+use intcode::intcode_disasm::disassemble_from;
+use intcode::intcode_io::{FnOutput, OutputDevice};
+use intcode::intcode_parse::load_program;
+
+const HELP: &str = "Commands:
+  s, step [n]             execute n instructions (default 1)
+  c, continue             run until a breakpoint, watchpoint or output condition is hit
+  b, break <addr>         set or remove a breakpoint
+  w, watch <addr>         set or remove a watchpoint on a memory cell
+  o, output <cond>        stop on output values: any, =v, !=v, <v or >v
+  o, output clear         remove all output conditions
+  in <value>...           queue input values
+  x, mem <addr> [n]       show n memory cells (default 1)
+  set <addr> <value>      patch a memory cell
+  l, list [addr] [n]      disassemble n words (default 10) from addr (default: next instruction)
+  i, info                 show registers, breakpoints, watchpoints and output conditions
+  h, help                 show this help
+  q, quit                 leave the debugger";
+
+fn parse<T: FromStr>(arg: Option<&&str>, default: Option<T>) -> Result<T, String> {
+    match arg {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("Not a valid number: {}", arg)),
+        None => default.ok_or_else(|| "Missing argument".to_string()),
+    }
+}
+
+fn parse_condition(arg: &str) -> Result<OutputCondition<i64>, String> {
+    let value = |text: &str| {
+        text.parse()
+            .map_err(|_| format!("Not a valid condition: {}", arg))
+    };
+    if arg == "any" {
+        Ok(OutputCondition::Any)
+    } else if let Some(text) = arg.strip_prefix("!=") {
+        Ok(OutputCondition::NotEqual(value(text)?))
+    } else if let Some(text) = arg.strip_prefix('=') {
+        Ok(OutputCondition::Equal(value(text)?))
+    } else if let Some(text) = arg.strip_prefix('<') {
+        Ok(OutputCondition::Less(value(text)?))
+    } else if let Some(text) = arg.strip_prefix('>') {
+        Ok(OutputCondition::Greater(value(text)?))
+    } else {
+        Err(format!("Not a valid condition: {}", arg))
+    }
+}
+
+fn print_position<O: OutputDevice<i64>>(debugger: &Debugger<i64, VecDeque<i64>, O>) {
+    let i_pointer = debugger.icc.i_pointer();
+    match debugger.current_instruction() {
+        Some(instruction) => println!("{:>5}: {}", i_pointer, instruction),
+        None => println!(
+            "{:>5}: invalid instruction {}",
+            i_pointer,
+            debugger.icc.memory().read(i_pointer)
+        ),
+    }
+}
+
+fn print_stop(stop: Stop<i64>) {
+    match stop {
+        Stop::Stepped => {}
+        Stop::Breakpoint(address) => println!("Breakpoint at {}", address),
+        Stop::Watchpoint { address, old, new } => {
+            println!("Watchpoint at {}: {} -> {}", address, old, new)
+        }
+        Stop::Output(value) => println!("Output condition matched by {}", value),
+        Stop::AwaitingInput => println!("Program is waiting for input (use: in <value>)"),
+        Stop::Halted => println!("Program has halted"),
+    }
+}
+
+fn execute<O: OutputDevice<i64>>(
+    debugger: &mut Debugger<i64, VecDeque<i64>, O>,
+    command: &str,
+    args: &[&str],
+) -> Result<(), String> {
+    match command {
+        "s" | "step" => {
+            let stop = debugger
+                .step(parse(args.first(), Some(1))?)
+                .map_err(|err| err.to_string())?;
+            print_stop(stop);
+            print_position(debugger);
+        }
+        "c" | "continue" => {
+            let stop = debugger.cont().map_err(|err| err.to_string())?;
+            print_stop(stop);
+            print_position(debugger);
+        }
+        "b" | "break" => {
+            let address = parse(args.first(), None)?;
+            let set = debugger.toggle_breakpoint(address);
+            println!(
+                "Breakpoint at {} {}",
+                address,
+                if set { "set" } else { "removed" }
+            );
+        }
+        "w" | "watch" => {
+            let address = parse(args.first(), None)?;
+            let set = debugger.toggle_watchpoint(address);
+            println!(
+                "Watchpoint at {} {}",
+                address,
+                if set { "set" } else { "removed" }
+            );
+        }
+        "o" | "output" => match args.first() {
+            Some(&"clear") => debugger.clear_output_conditions(),
+            Some(arg) => debugger.add_output_condition(parse_condition(arg)?),
+            None => return Err("Missing condition".to_string()),
+        },
+        "in" => {
+            for arg in args {
+                debugger.icc.input.push_back(parse(Some(arg), None)?);
+            }
+        }
+        "x" | "mem" => {
+            let address: usize = parse(args.first(), None)?;
+            let count: usize = parse(args.get(1), Some(1))?;
+            for address in address..address.saturating_add(count) {
+                println!("{:>5}: {}", address, debugger.icc.memory().read(address));
+            }
+        }
+        "set" => {
+            let address = parse(args.first(), None)?;
+            let value = parse(args.get(1), None)?;
+            debugger.icc.patch_memory(address, value);
+        }
+        "l" | "list" => {
+            let address: usize = parse(args.first(), Some(debugger.icc.i_pointer()))?;
+            let count: usize = parse(args.get(1), Some(10))?;
+            // decode only the listed words, and the parameters of the last instruction
+            let end = address.saturating_add(count.max(4)).saturating_add(3);
+            let words: Vec<i64> = (address..end)
+                .map(|a| debugger.icc.memory().read(a))
+                .collect();
+            let mut listing = disassemble_from(&words, &[0]);
+            for line in &mut listing.lines {
+                line.address += address;
+            }
+            for line in listing
+                .lines
+                .iter()
+                .filter(|line| line.address < address.saturating_add(count))
+            {
+                println!("{}", line);
+            }
+        }
+        "i" | "info" => {
+            println!("i_pointer: {}", debugger.icc.i_pointer());
+            println!("relative base: {}", debugger.icc.relative_base());
+            println!("steps: {}", debugger.icc.steps());
+            println!("queued input: {:?}", debugger.icc.input);
+            println!("breakpoints: {:?}", debugger.breakpoints());
+            println!("watchpoints: {:?}", debugger.watchpoints());
+            let conditions: Vec<String> = debugger
+                .output_conditions()
+                .iter()
+                .map(|c| c.to_string())
+                .collect();
+            println!("output conditions: {}", conditions.join(" "));
+            print_position(debugger);
+        }
+        "h" | "help" => println!("{}", HELP),
+        _ => return Err(format!("Unknown command: {} (try help)", command)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} <program-file>", args[0]);
+        process::exit(2);
+    }
+
+    let program: Vec<i64> = load_program(&args[1]).unwrap_or_else(|err| {
+        eprintln!("Cannot load {}: {}", args[1], err);
+        process::exit(1);
+    });
+
+    let output = FnOutput(|value: i64| println!("Output: {}", value));
+    let icc = IntCodeComputer::with_io(&program, VecDeque::new(), output);
+    let mut debugger = Debugger::new(icc);
+    print_position(&debugger);
+
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.split_first() {
+            None => continue,
+            Some((&"q", _)) | Some((&"quit", _)) => break,
+            Some((command, args)) => {
+                if let Err(message) = execute(&mut debugger, command, args) {
+                    println!("{}", message);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Disassembler for Intcode programs.
+//!
+//! Usage: `intcode-disasm <program-file> [entry-address...]`
+//!
+//! The program file holds the comma-separated program, as given by the puzzles (`-` for stdin).
+//! Additional entry addresses make code behind computed jumps visible.
+
+use std::env;
+use std::process;
+
+use intcode::intcode_disasm::disassemble_from;
+use intcode::intcode_parse::load_program_and_entries;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (program, entries) = load_program_and_entries::<i64>(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    });
+
+    print!("{}", disassemble_from(&program, &entries));
+}
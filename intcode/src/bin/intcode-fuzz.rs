@@ -0,0 +1,58 @@
+//! Differential fuzzer of the Intcode interpreters.
+//!
+//! Usage: `intcode-fuzz [--seed N] [--runs N] [--instructions N]`
+//!
+//! Generates random, well-formed programs and runs each on a reference interpreter and on the
+//! Intcode computer in several configurations (see `intcode_fuzz::check`). The first program, on
+//! which they disagree, is shrunk and printed with its input, comma-separated as the puzzles give
+//! programs, so it can be replayed with the other tools.
+
+use std::env;
+use std::process;
+
+use intcode::intcode_fuzz::{fuzz, FuzzOptions};
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} [--seed N] [--runs N] [--instructions N]",
+        program
+    );
+    process::exit(2);
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut seed = 1;
+    let mut runs = 1000;
+    let mut options = FuzzOptions::default();
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        let mut number = || {
+            rest.next()
+                .and_then(|n| n.parse::<u64>().ok())
+                .unwrap_or_else(|| usage(&args[0]))
+        };
+        match arg.as_str() {
+            "--seed" => seed = number(),
+            "--runs" => runs = number(),
+            "--instructions" => options.instructions = number() as usize,
+            _ => usage(&args[0]),
+        }
+    }
+
+    match fuzz(seed, runs, &options) {
+        None => println!("{} programs, all interpreters agree", runs),
+        Some(failure) => {
+            println!("Run {} of seed {} failed", failure.run, seed);
+            println!("{}", failure.mismatch);
+            println!("Program: {}", join(&failure.program));
+            println!("Input: {}", join(&failure.input));
+            process::exit(1);
+        }
+    }
+}
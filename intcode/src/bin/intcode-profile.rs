@@ -0,0 +1,73 @@
+//! Profiler for Intcode programs.
+//!
+//! Usage: `intcode-profile <program-file> [input...] [--json] [--coverage] [--top N]`
+//!
+//! The program file holds the comma-separated program, as given by the puzzles (`-` for stdin).
+//! The inputs are queued before the run, and the output values are printed to stderr. Afterwards
+//! the profile is printed as text table (default) or JSON, or as disassembler listing with the
+//! number of executions of every instruction.
+
+use std::collections::VecDeque;
+use std::env;
+use std::process;
+
+use intcode::intcode_cmp::IntCodeComputer;
+use intcode::intcode_io::FnOutput;
+use intcode::intcode_parse::load_program;
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} <program-file> [input...] [--json] [--coverage] [--top N]",
+        program
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut program_file = None;
+    let mut inputs = VecDeque::new();
+    let mut json = false;
+    let mut coverage = false;
+    let mut top = 10;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--coverage" => coverage = true,
+            "--top" => {
+                top = rest
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| usage(&args[0]))
+            }
+            _ if program_file.is_none() => program_file = Some(arg),
+            _ => inputs.push_back(arg.parse::<i64>().unwrap_or_else(|_| {
+                eprintln!("Not a valid input: {}", arg);
+                process::exit(2);
+            })),
+        }
+    }
+    let program_file = program_file.unwrap_or_else(|| usage(&args[0]));
+
+    let program: Vec<i64> = load_program(program_file).unwrap_or_else(|err| {
+        eprintln!("Cannot load {}: {}", program_file, err);
+        process::exit(1);
+    });
+
+    let output = FnOutput(|value: i64| eprintln!("Output: {}", value));
+    let mut icc = IntCodeComputer::with_io(&program, inputs, output);
+    icc.set_profiling(true);
+    if let Err(err) = icc.compute() {
+        eprintln!("{}", err);
+    }
+
+    let profile = icc.take_profile().unwrap();
+    if json {
+        println!("{}", profile.to_json());
+    } else if coverage {
+        print!("{}", profile.coverage(&program));
+    } else {
+        print!("{}", profile.to_text(top));
+    }
+}
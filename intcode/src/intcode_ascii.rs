@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::intcode_cmp::{IntCodeComputer, RunState};
+use crate::intcode_error::IntcodeError;
+use crate::intcode_word::Word;
+
+/// Output of a program speaking ASCII.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiOutput<W> {
+    /// Line of text, without the newline.
+    Line(String),
+    /// Value outside of the ASCII range, e.g. the numeric result of a puzzle.
+    Value(W),
+}
+
+/// Error returned for commands, which are not pure ASCII.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAscii(pub char);
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not an ASCII character: {:?}", self.0)
+    }
+}
+
+impl Error for NotAscii {}
+
+/// Adapter for Intcode programs, which output character codes and read lines of text.
+///
+/// ```
+/// use intcode::intcode_ascii::{AsciiComputer, AsciiOutput};
+/// use intcode::intcode_cmp::RunState;
+///
+/// // print "Hi", then a value, then echo one character of input
+/// let program = [104, 72, 104, 105, 104, 10, 104, 1000, 3, 13, 4, 13, 99, 0];
+///
+/// let mut ascii = AsciiComputer::new(&program);
+/// let (state, output) = ascii.run().unwrap();
+/// assert_eq!(RunState::AwaitingInput, state);
+/// assert_eq!(
+///     vec![AsciiOutput::Line("Hi".to_string()), AsciiOutput::Value(1000)],
+///     output
+/// );
+///
+/// ascii.send("x").unwrap();
+/// let (state, output) = ascii.run().unwrap();
+/// assert_eq!(RunState::Halted, state);
+/// assert_eq!(vec![AsciiOutput::Line("x".to_string())], output);
+/// ```
+#[derive(Debug)]
+pub struct AsciiComputer<W: Word = i64> {
+    pub icc: IntCodeComputer<W>,
+    line: String,
+}
+
+impl<W: Word> AsciiComputer<W> {
+    /// * `program` - program instructions and data
+    pub fn new(program: &[W]) -> AsciiComputer<W> {
+        let mut icc = IntCodeComputer::new(program);
+        icc.set_return_control(true);
+        AsciiComputer {
+            icc,
+            line: String::new(),
+        }
+    }
+
+    /// Queue a command as input, encoded as character codes with a trailing newline.
+    ///
+    /// Nothing is queued, if the command contains any character outside of ASCII.
+    pub fn send(&mut self, command: &str) -> Result<(), NotAscii> {
+        if let Some(c) = command.chars().find(|c| !c.is_ascii()) {
+            return Err(NotAscii(c));
+        }
+        for c in command.chars().chain(Some('\n')) {
+            self.icc.input.push_back(W::from(c as i32));
+        }
+        Ok(())
+    }
+
+    /// Run the program until it needs input or halts.
+    ///
+    /// Returns why it stopped (`AwaitingInput` or `Halted`) and the output in the meantime. Text
+    /// is buffered until the end of the line, but incomplete lines (e.g. prompts without a
+    /// newline) are returned as well when the program stops.
+    pub fn run(&mut self) -> Result<(RunState<W>, Vec<AsciiOutput<W>>), IntcodeError> {
+        let mut output = Vec::new();
+        loop {
+            match self.icc.run()? {
+                RunState::Output(value) => match value.to_i128() {
+                    10 => output.push(AsciiOutput::Line(self.line.split_off(0))),
+                    code @ 0..=127 => self.line.push(code as u8 as char),
+                    _ => output.push(AsciiOutput::Value(value)),
+                },
+                state => {
+                    if !self.line.is_empty() {
+                        output.push(AsciiOutput::Line(self.line.split_off(0)));
+                    }
+                    return Ok((state, output));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_ascii::{AsciiComputer, AsciiOutput, NotAscii};
+    use crate::intcode_asm::assemble;
+    use crate::intcode_cmp::RunState;
+
+    // print "> " and echo every line of input in upper case, until an empty line is read
+    const SHOUT: &str = "
+    start:  out #62
+            out #32
+            in [char]
+            eq [char], #10, [flag]
+            jnz [flag], #done
+    loop:   lt #96, [char], [flag]
+            jz [flag], #print
+            add [char], #-32, [char]
+    print:  out [char]
+            in [char]
+            eq [char], #10, [flag]
+            jz [flag], #loop
+            out #10
+            jz #0, #start
+    done:   hlt
+    char:   data 0
+    flag:   data 0
+    ";
+
+    fn lines(texts: &[&str]) -> Vec<AsciiOutput<i64>> {
+        texts
+            .iter()
+            .map(|text| AsciiOutput::Line(text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dialog() {
+        let mut ascii = AsciiComputer::new(&assemble(SHOUT).unwrap());
+        assert_eq!(Ok((RunState::AwaitingInput, lines(&["> "]))), ascii.run());
+
+        ascii.send("hello, World").unwrap();
+        assert_eq!(
+            Ok((RunState::AwaitingInput, lines(&["HELLO, WORLD", "> "]))),
+            ascii.run()
+        );
+
+        ascii.send("").unwrap();
+        assert_eq!(Ok((RunState::Halted, lines(&[]))), ascii.run());
+    }
+
+    #[test]
+    fn values_and_errors() {
+        let mut ascii = AsciiComputer::new(&[104, 65, 104, 128, 104, -1, 104, 66, 99]);
+        assert_eq!(
+            Ok((
+                RunState::Halted,
+                vec![
+                    AsciiOutput::Value(128),
+                    AsciiOutput::Value(-1),
+                    AsciiOutput::Line("AB".to_string()),
+                ]
+            )),
+            ascii.run()
+        );
+
+        assert_eq!(Err(NotAscii('ä')), ascii.send("bär"));
+        assert!(ascii.icc.input.is_empty());
+    }
+}
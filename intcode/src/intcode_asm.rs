@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::intcode_disasm::{op_by_mnemonic, Mode, OpInfo};
+use crate::intcode_word::Word;
+
+/// Largest program `assemble` produces, in words, so a `.zero` with a huge count fails instead of
+/// exhausting the memory.
+pub const MAX_PROGRAM_SIZE: usize = 1 << 24;
+
+/// Assembler for Intcode programs.
+///
+/// The assembly language uses the mnemonics of the disassembler, so its listings can be assembled
+/// again. Every line holds at most one statement:
+///
+/// ```text
+/// ; comments start with a semicolon
+/// start:                  ; labels name the address of the next word
+///     in [counter]        ; position mode
+///     out rb-1            ; relative mode: rb, rb+N or rb-N
+///     add [counter], #-1, [counter]
+///     jnz [counter], #start+2
+///     hlt
+/// counter: .data 0        ; .data (or data) with a list of values
+/// buffer: .zero 16        ; .zero with the number of zero words
+/// ```
+///
+/// Operand values are integers or labels, optionally with added or subtracted integers or labels
+/// (`#start+2`). A line may start with the address of its first word (`12:`), as printed by the
+/// disassembler; it is checked against the actual address. Programs are limited to
+/// `MAX_PROGRAM_SIZE` words.
+///
+/// ```
+/// use intcode::intcode_asm::assemble;
+///
+/// let program: Vec<i32> = assemble("out #42\nhlt").unwrap();
+/// assert_eq!(vec![104, 42, 99], program);
+/// ```
+pub fn assemble<W: Word>(source: &str) -> Result<Vec<W>, AsmError> {
+    // first pass: parse statements and assign addresses to labels
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+    for (number, text) in source.lines().enumerate() {
+        let line = number + 1;
+        let error = |kind| AsmError { line, kind };
+        let (prefixes, statement) = parse_line(text).map_err(error)?;
+        for prefix in prefixes {
+            match prefix {
+                Prefix::Label(name) => {
+                    if labels.insert(name.clone(), address as i128).is_some() {
+                        return Err(error(AsmErrorKind::DuplicateLabel(name)));
+                    }
+                }
+                Prefix::Address(expected) => {
+                    if expected != address {
+                        return Err(error(AsmErrorKind::AddressMismatch {
+                            expected,
+                            found: address,
+                        }));
+                    }
+                }
+            }
+        }
+        if let Some(statement) = statement {
+            address = address
+                .checked_add(statement.size())
+                .filter(|&end| end <= MAX_PROGRAM_SIZE)
+                .ok_or_else(|| error(AsmErrorKind::ProgramTooLarge))?;
+            statements.push((line, statement));
+        }
+    }
+
+    // second pass: resolve operands and encode
+    let mut program = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        let error = |kind| AsmError { line, kind };
+        let word = |expr: &Expr| -> Result<W, AsmError> {
+            let value = expr.eval(&labels).map_err(error)?;
+            W::from_i128(value).ok_or_else(|| error(AsmErrorKind::ValueOutOfRange(value)))
+        };
+        match statement {
+            Statement::Instruction(op, operands) => {
+                let mut instruction = op.opcode;
+                let mut factor = 100;
+                for (mode, _) in &operands {
+                    instruction += mode.digit() * factor;
+                    factor *= 10;
+                }
+                program.push(word(&Expr::number(instruction))?);
+                for (_, expr) in &operands {
+                    program.push(word(expr)?);
+                }
+            }
+            Statement::Data(values) => {
+                for expr in &values {
+                    program.push(word(expr)?);
+                }
+            }
+            Statement::Zero(count) => program.resize(program.len() + count, W::default()),
+        }
+    }
+    Ok(program)
+}
+
+/// Error in the assembly source, with the line number (counting from one).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount { expected: usize, found: usize },
+    BadOperand(String),
+    WriteInImmediateMode(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    AddressMismatch { expected: usize, found: usize },
+    ValueOutOfRange(i128),
+    ProgramTooLarge,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic {}", mnemonic),
+            AsmErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            AsmErrorKind::BadOperand(operand) => write!(f, "bad operand {:?}", operand),
+            AsmErrorKind::WriteInImmediateMode(operand) => {
+                write!(f, "cannot write to immediate operand {}", operand)
+            }
+            AsmErrorKind::UndefinedLabel(label) => write!(f, "undefined label {}", label),
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "duplicate label {}", label),
+            AsmErrorKind::AddressMismatch { expected, found } => write!(
+                f,
+                "line is marked with address {}, but is at address {}",
+                expected, found
+            ),
+            AsmErrorKind::ValueOutOfRange(value) => {
+                write!(f, "value {} does not fit into a word", value)
+            }
+            AsmErrorKind::ProgramTooLarge => {
+                write!(f, "program exceeds {} words", MAX_PROGRAM_SIZE)
+            }
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug)]
+enum Prefix {
+    Label(String),
+    Address(usize),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Instruction(&'static OpInfo, Vec<(Mode, Expr)>),
+    Data(Vec<Expr>),
+    Zero(usize),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => 1 + operands.len(),
+            Statement::Data(values) => values.len(),
+            Statement::Zero(count) => *count,
+        }
+    }
+}
+
+/// Sum of integers and labels, each with a sign.
+#[derive(Debug)]
+struct Expr {
+    terms: Vec<(i128, Term)>,
+}
+
+#[derive(Debug)]
+enum Term {
+    Number(i128),
+    Label(String),
+}
+
+impl Expr {
+    fn number(value: i128) -> Expr {
+        Expr {
+            terms: vec![(1, Term::Number(value))],
+        }
+    }
+
+    fn parse(text: &str) -> Option<Expr> {
+        let mut terms = Vec::new();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            let sign = match rest.chars().next() {
+                Some('+') => 1,
+                Some('-') => -1,
+                _ if terms.is_empty() => 0,
+                _ => return None,
+            };
+            if sign != 0 {
+                rest = rest[1..].trim_start();
+            }
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let token = &rest[..end];
+            let term = if token.starts_with(|c: char| c.is_ascii_digit()) {
+                Term::Number(token.parse().ok()?)
+            } else if is_identifier(token) {
+                Term::Label(token.to_string())
+            } else {
+                return None;
+            };
+            terms.push((if sign == -1 { -1 } else { 1 }, term));
+            rest = rest[end..].trim_start();
+        }
+        if terms.is_empty() {
+            None
+        } else {
+            Some(Expr { terms })
+        }
+    }
+
+    fn eval(&self, labels: &HashMap<String, i128>) -> Result<i128, AsmErrorKind> {
+        let mut sum = 0_i128;
+        for (sign, term) in &self.terms {
+            let value = match term {
+                Term::Number(value) => *value,
+                Term::Label(name) => *labels
+                    .get(name)
+                    .ok_or_else(|| AsmErrorKind::UndefinedLabel(name.clone()))?,
+            };
+            // an overflowing sum is reported as the nearest value, which does not fit either
+            sum = sign
+                .checked_mul(value)
+                .and_then(|term| sum.checked_add(term))
+                .ok_or_else(|| {
+                    AsmErrorKind::ValueOutOfRange(sum.saturating_add(sign.saturating_mul(value)))
+                })?;
+        }
+        Ok(sum)
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split a line into its labels or address marks, and its statement.
+fn parse_line(text: &str) -> Result<(Vec<Prefix>, Option<Statement>), AsmErrorKind> {
+    let mut rest = match text.find(';') {
+        Some(comment) => &text[..comment],
+        None => text,
+    }
+    .trim();
+
+    let mut prefixes = Vec::new();
+    while let Some(colon) = rest.find(':') {
+        let name = rest[..colon].trim();
+        if is_identifier(name) {
+            prefixes.push(Prefix::Label(name.to_string()));
+        } else if let Ok(address) = name.parse() {
+            prefixes.push(Prefix::Address(address));
+        } else {
+            break;
+        }
+        rest = rest[colon + 1..].trim();
+    }
+    if rest.is_empty() {
+        return Ok((prefixes, None));
+    }
+
+    let (mnemonic, args) = match rest.find(char::is_whitespace) {
+        Some(space) => (&rest[..space], rest[space..].trim()),
+        None => (rest, ""),
+    };
+    let args: Vec<&str> = if args.is_empty() {
+        Vec::new()
+    } else {
+        args.split(',').map(str::trim).collect()
+    };
+
+    let statement = match mnemonic {
+        ".data" | "data" => Statement::Data(
+            args.iter()
+                .map(|arg| Expr::parse(arg).ok_or_else(|| bad_operand(arg)))
+                .collect::<Result<_, _>>()?,
+        ),
+        ".zero" => match args.as_slice() {
+            [count] => Statement::Zero(count.parse().map_err(|_| bad_operand(count))?),
+            _ => {
+                return Err(AsmErrorKind::WrongOperandCount {
+                    expected: 1,
+                    found: args.len(),
+                })
+            }
+        },
+        _ => {
+            let op = op_by_mnemonic(mnemonic)
+                .ok_or_else(|| AsmErrorKind::UnknownMnemonic(mnemonic.to_string()))?;
+            if args.len() != op.params {
+                return Err(AsmErrorKind::WrongOperandCount {
+                    expected: op.params,
+                    found: args.len(),
+                });
+            }
+            let mut operands = Vec::with_capacity(args.len());
+            for (number, arg) in args.iter().enumerate() {
+                let operand = parse_operand(arg).ok_or_else(|| bad_operand(arg))?;
+                if op.writes && number + 1 == op.params && operand.0 == Mode::Immediate {
+                    return Err(AsmErrorKind::WriteInImmediateMode(arg.to_string()));
+                }
+                operands.push(operand);
+            }
+            Statement::Instruction(op, operands)
+        }
+    };
+    Ok((prefixes, Some(statement)))
+}
+
+fn bad_operand(arg: &str) -> AsmErrorKind {
+    AsmErrorKind::BadOperand(arg.to_string())
+}
+
+/// Parse `#expr`, `[expr]`, `rb`, `rb+expr` or `rb-expr`.
+fn parse_operand(arg: &str) -> Option<(Mode, Expr)> {
+    if let Some(expr) = arg.strip_prefix('#') {
+        Some((Mode::Immediate, Expr::parse(expr)?))
+    } else if let Some(expr) = arg.strip_prefix('[') {
+        Some((Mode::Position, Expr::parse(expr.strip_suffix(']')?)?))
+    } else if let Some(expr) = arg.strip_prefix("rb") {
+        let expr = expr.trim();
+        if expr.is_empty() {
+            Some((Mode::Relative, Expr::number(0)))
+        } else if expr.starts_with('+') || expr.starts_with('-') {
+            Some((Mode::Relative, Expr::parse(expr)?))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_asm::{assemble, AsmError, AsmErrorKind};
+    use crate::intcode_cmp::IntCodeComputer;
+    use crate::intcode_disasm::{disassemble, disassemble_from};
+
+    #[test]
+    fn countdown() {
+        let source = "
+            ; count down from the input value to one
+                    in [counter]
+            loop:   out [counter]
+                    add [counter], #-1, [counter]
+                    jnz [counter], #loop
+                    hlt
+            counter: .data 0
+        ";
+        let program: Vec<i32> = assemble(source).unwrap();
+        assert_eq!(
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0],
+            program
+        );
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(3);
+        icc.compute().unwrap();
+        assert_eq!(vec![3, 2, 1], icc.output.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn directives_and_relative_mode() {
+        let source = "
+            arb #buffer+2
+            out rb-2
+            out rb
+            out rb+1
+            hlt
+            buffer: .zero 2 ; two zeros
+            .data 7, end-buffer
+            end:
+        ";
+        let program: Vec<i64> = assemble(source).unwrap();
+        assert_eq!(
+            vec![109, 11, 204, -2, 204, 0, 204, 1, 99, 0, 0, 7, 4],
+            program
+        );
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(vec![0, 7, 4], icc.output.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn errors() {
+        let error = |line, kind| Err::<Vec<i32>, _>(AsmError { line, kind });
+
+        assert_eq!(
+            error(2, AsmErrorKind::UndefinedLabel("nowhere".to_string())),
+            assemble("hlt\njz #0, #nowhere")
+        );
+        assert_eq!(
+            error(1, AsmErrorKind::BadOperand("5".to_string())),
+            assemble("out 5")
+        );
+        assert_eq!(
+            error(1, AsmErrorKind::BadOperand("[x".to_string())),
+            assemble("out [x")
+        );
+        assert_eq!(
+            error(3, AsmErrorKind::UnknownMnemonic("jmp".to_string())),
+            assemble("\n\njmp #0")
+        );
+        assert_eq!(
+            error(
+                1,
+                AsmErrorKind::WrongOperandCount {
+                    expected: 3,
+                    found: 2
+                }
+            ),
+            assemble("add #1, #2")
+        );
+        assert_eq!(
+            error(1, AsmErrorKind::WriteInImmediateMode("#3".to_string())),
+            assemble("add #1, #2, #3")
+        );
+        assert_eq!(
+            error(2, AsmErrorKind::DuplicateLabel("a".to_string())),
+            assemble("a: hlt\na: hlt")
+        );
+        assert_eq!(
+            error(
+                2,
+                AsmErrorKind::AddressMismatch {
+                    expected: 1,
+                    found: 2
+                }
+            ),
+            assemble("0: out #1\n1: hlt")
+        );
+        assert_eq!(
+            error(1, AsmErrorKind::ValueOutOfRange(3_000_000_000)),
+            assemble(".data 3000000000")
+        );
+        assert_eq!(
+            error(1, AsmErrorKind::ValueOutOfRange(i128::MAX)),
+            assemble(".data 170141183460469231731687303715884105727+1")
+        );
+        assert_eq!(
+            error(2, AsmErrorKind::ProgramTooLarge),
+            assemble("hlt\n.zero 18446744073709551615")
+        );
+        assert_eq!(
+            error(2, AsmErrorKind::ProgramTooLarge),
+            assemble(".zero 16777216\nhlt")
+        );
+        assert_eq!(
+            "line 2: undefined label nowhere",
+            assemble::<i32>("hlt\njz #0, #nowhere")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        let listing = disassemble(&program).to_string();
+        assert_eq!(program.to_vec(), assemble::<i32>(&listing).unwrap());
+
+        let listing = disassemble_from(&program, &[0, 22, 31, 36]).to_string();
+        assert!(listing.contains("   31: out #999"));
+        assert_eq!(program.to_vec(), assemble::<i32>(&listing).unwrap());
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::intcode_disasm::{reachable, Flow, Instruction, Mode};
+use crate::intcode_word::Word;
+
+/// Kind of an edge between two basic blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    Next,
+    /// A jump with a target in immediate mode is taken.
+    Jump,
+}
+
+/// Edge to the basic block starting at `target`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// Sequence of instructions, which is only entered at its first instruction and only left after
+/// its last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block<W> {
+    /// Instructions with their addresses
+    pub instructions: Vec<(usize, Instruction<W>)>,
+    /// Blocks (or invalid addresses, see `Cfg::invalid`), which may follow, as far as they are
+    /// known statically
+    pub edges: Vec<Edge>,
+    /// Whether the block ends with a jump to a computed target, i.e. with an unresolved edge
+    pub computed_jump: bool,
+}
+
+impl<W: Word> Block<W> {
+    /// Address of the first instruction.
+    pub fn start(&self) -> usize {
+        self.instructions[0].0
+    }
+
+    /// Address behind the last instruction.
+    pub fn end(&self) -> usize {
+        let (address, instruction) = self.instructions.last().unwrap();
+        address + instruction.size()
+    }
+}
+
+/// Control-flow graph of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg<W> {
+    /// Basic blocks, sorted by address
+    pub blocks: Vec<Block<W>>,
+    /// Address ranges, which are not part of any reachable instruction, e.g. data
+    pub unreachable: Vec<Range<usize>>,
+    /// Addresses, which control flow reaches, but which hold no valid instruction, e.g. because
+    /// the program writes it before
+    pub invalid: BTreeSet<usize>,
+    /// Words of reachable instructions and invalid addresses, which reachable instructions write
+    /// to. Only writes in position mode are known statically.
+    pub self_modified: BTreeSet<usize>,
+}
+
+/// Edges leaving an instruction, and whether it jumps to a computed target.
+fn edges<W: Word>(address: usize, instruction: &Instruction<W>) -> (Vec<Edge>, bool) {
+    let mut edges = Vec::new();
+    let mut computed = false;
+    for flow in instruction.flow(address) {
+        match flow {
+            Flow::Next(target) => edges.push(Edge {
+                target,
+                kind: EdgeKind::Next,
+            }),
+            Flow::Jump(target) => edges.push(Edge {
+                target,
+                kind: EdgeKind::Jump,
+            }),
+            Flow::ComputedJump => computed = true,
+        }
+    }
+    (edges, computed)
+}
+
+/// Build the control-flow graph of a program.
+///
+/// Control flow is followed from the entry points as in `disassemble_from`: jumps with targets in
+/// immediate mode are resolved, jumps to computed targets are flagged on their block.
+///
+/// * `program` - program instructions and data
+/// * `entries` - addresses to start following the control flow from
+///
+/// ```
+/// use intcode::intcode_cfg::control_flow_graph;
+///
+/// // jump over data, then halt
+/// let cfg = control_flow_graph(&[1105, 1, 4, 42, 99], &[0]);
+/// assert_eq!(2, cfg.blocks.len());
+/// assert_eq!(4, cfg.blocks[0].edges[0].target);
+/// assert_eq!(vec![3..4], cfg.unreachable);
+/// ```
+pub fn control_flow_graph<W: Word>(program: &[W], entries: &[usize]) -> Cfg<W> {
+    let code = reachable(program, entries);
+    let decode = |address: usize| Instruction::decode(&program[address..]).unwrap();
+
+    // blocks start at entry points, at jump targets and behind jumps
+    let mut leaders: BTreeSet<usize> = entries
+        .iter()
+        .copied()
+        .filter(|address| code.contains(address))
+        .collect();
+    for &address in &code {
+        let instruction = decode(address);
+        if let 5 | 6 = instruction.op.opcode {
+            let successors = instruction.successors(address);
+            leaders.extend(
+                successors
+                    .into_iter()
+                    .filter(|target| code.contains(target)),
+            );
+        }
+    }
+
+    let mut blocks = Vec::new();
+    let mut invalid = BTreeSet::new();
+    for &start in &leaders {
+        let mut instructions = Vec::new();
+        let mut address = start;
+        loop {
+            let instruction = decode(address);
+            let next = address + instruction.size();
+            let opcode = instruction.op.opcode;
+            instructions.push((address, instruction));
+            if let 5 | 6 | 99 = opcode {
+                break;
+            }
+            if leaders.contains(&next) || !code.contains(&next) {
+                break;
+            }
+            address = next;
+        }
+
+        let (address, last) = instructions.last().unwrap();
+        let (edges, computed_jump) = edges(*address, last);
+        for edge in &edges {
+            if !code.contains(&edge.target) {
+                invalid.insert(edge.target);
+            }
+        }
+        blocks.push(Block {
+            instructions,
+            edges,
+            computed_jump,
+        });
+    }
+
+    // words of the reachable instructions, and the words written to in position mode
+    let mut words = BTreeSet::new();
+    let mut written = BTreeSet::new();
+    for &address in &code {
+        let instruction = decode(address);
+        for word in address..address + instruction.size() {
+            words.insert(word);
+        }
+        if instruction.op.writes {
+            let target = instruction.operands.last().unwrap();
+            if target.mode == Mode::Position {
+                if let Ok(target) = usize::try_from(target.value.to_i128()) {
+                    written.insert(target);
+                }
+            }
+        }
+    }
+    let self_modified = written
+        .into_iter()
+        .filter(|word| words.contains(word) || invalid.contains(word))
+        .collect();
+
+    let mut unreachable: Vec<Range<usize>> = Vec::new();
+    for address in (0..program.len()).filter(|address| !words.contains(address)) {
+        match unreachable.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => unreachable.push(address..address + 1),
+        }
+    }
+
+    Cfg {
+        blocks,
+        unreachable,
+        invalid,
+        self_modified,
+    }
+}
+
+impl<W: Word> Cfg<W> {
+    /// Graph in the DOT language of Graphviz, e.g. to render with `dot -Tsvg`.
+    ///
+    /// Every block is a node labelled with its disassembly. Instructions, which the program
+    /// overwrites, are marked with `*` and their blocks are drawn in red. Jumps are drawn solid,
+    /// fall-throughs dashed, and computed jumps lead to a `?` node. Unreachable regions are grey
+    /// nodes without edges, invalid addresses red nodes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot += "    node [shape=box, fontname=\"monospace\"];\n";
+        for block in &self.blocks {
+            let mut label = String::new();
+            let mut modified = false;
+            for (address, instruction) in &block.instructions {
+                let marker = if (*address..address + instruction.size())
+                    .any(|word| self.self_modified.contains(&word))
+                {
+                    modified = true;
+                    "*"
+                } else {
+                    " "
+                };
+                write!(label, "{}{:>5}: {}\\l", marker, address, instruction).unwrap();
+            }
+            let color = if modified { ", color=red" } else { "" };
+            writeln!(
+                dot,
+                "    b{} [label=\"{}\"{}];",
+                block.start(),
+                label,
+                color
+            )
+            .unwrap();
+            for edge in &block.edges {
+                let style = match edge.kind {
+                    EdgeKind::Jump => "",
+                    EdgeKind::Next => " [style=dashed]",
+                };
+                writeln!(dot, "    b{} -> b{}{};", block.start(), edge.target, style).unwrap();
+            }
+            if block.computed_jump {
+                let start = block.start();
+                writeln!(dot, "    computed{} [label=\"?\", shape=circle];", start).unwrap();
+                writeln!(dot, "    b{} -> computed{} [style=dotted];", start, start).unwrap();
+            }
+        }
+        for address in &self.invalid {
+            let marker = if self.self_modified.contains(address) {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(
+                dot,
+                "    b{} [label=\"{}{:>5}: invalid\\l\", color=red];",
+                address, marker, address
+            )
+            .unwrap();
+        }
+        for range in &self.unreachable {
+            writeln!(
+                dot,
+                "    data{} [label=\"{}..{}: {} unreachable words\", style=filled, fillcolor=lightgrey];",
+                range.start,
+                range.start,
+                range.end,
+                range.len()
+            )
+            .unwrap();
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_cfg::{control_flow_graph, Edge, EdgeKind};
+
+    fn starts(program: &[i32], entries: &[usize]) -> Vec<usize> {
+        let cfg = control_flow_graph(program, entries);
+        cfg.blocks.iter().map(|block| block.start()).collect()
+    }
+
+    #[test]
+    fn computed_jump() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let cfg = control_flow_graph(&program, &[0]);
+
+        assert_eq!(2, cfg.blocks.len());
+        assert_eq!(0..5, cfg.blocks[0].start()..cfg.blocks[0].end());
+        assert!(cfg.blocks[0].computed_jump);
+        assert_eq!(
+            vec![Edge {
+                target: 5,
+                kind: EdgeKind::Next
+            }],
+            cfg.blocks[0].edges
+        );
+        assert_eq!(5..12, cfg.blocks[1].start()..cfg.blocks[1].end());
+        assert!(!cfg.blocks[1].computed_jump);
+        assert!(cfg.blocks[1].edges.is_empty());
+        assert_eq!(vec![12..16], cfg.unreachable);
+        assert!(cfg.self_modified.is_empty());
+
+        // the target of the computed jump, given as entry point
+        assert_eq!(vec![0, 5, 9], starts(&program, &[0, 9]));
+    }
+
+    #[test]
+    fn static_jumps_and_self_modification() {
+        // count [20] up to 3 in a loop, then patch the operand of the output instruction
+        let program = [
+            1001, 20, 1, 20, 1007, 20, 3, 21, 1005, 21, 0, 1101, 0, 42, 16, 104, 99, 99,
+        ];
+        let cfg = control_flow_graph(&program, &[0]);
+
+        assert_eq!(
+            vec![0, 11],
+            cfg.blocks.iter().map(|b| b.start()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                Edge {
+                    target: 0,
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    target: 11,
+                    kind: EdgeKind::Next
+                }
+            ],
+            cfg.blocks[0].edges
+        );
+        assert_eq!(11..18, cfg.blocks[1].start()..cfg.blocks[1].end());
+        assert!(cfg.self_modified.contains(&16));
+        assert!(cfg.unreachable.is_empty());
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph intcode {\n"));
+        assert!(dot.contains("    b0 -> b0;\n"));
+        assert!(dot.contains("    b0 -> b11 [style=dashed];\n"));
+        assert!(dot.contains("*   15: out #99\\l"));
+        assert!(dot.contains(", color=red];\n"));
+    }
+
+    #[test]
+    fn invalid_before_modification() {
+        // the first instruction turns the word at 4 from 1100 into the valid instruction 1101
+        let program = [1101, 1, 1100, 4, 1100, 0, 0, 0, 99];
+        let cfg = control_flow_graph(&program, &[0]);
+
+        assert_eq!(1, cfg.blocks.len());
+        assert_eq!(
+            vec![Edge {
+                target: 4,
+                kind: EdgeKind::Next
+            }],
+            cfg.blocks[0].edges
+        );
+        assert_eq!(vec![4], cfg.invalid.into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![4], cfg.self_modified.into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![4..9], cfg.unreachable);
+    }
+
+    #[test]
+    fn dot_unresolved_and_unreachable() {
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let dot = control_flow_graph(&program, &[0]).to_dot();
+        let expected = r#"digraph intcode {
+    node [shape=box, fontname="monospace"];
+    b0 [label="     0: in [12]\l     2: jz [12], [15]\l"];
+    b0 -> b5 [style=dashed];
+    computed0 [label="?", shape=circle];
+    b0 -> computed0 [style=dotted];
+    b5 [label="     5: add [13], [14], [13]\l     9: out [13]\l    11: hlt\l"];
+    data12 [label="12..16: 4 unreachable words", style=filled, fillcolor=lightgrey];
+}
+"#;
+        assert_eq!(expected, dot);
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use crate::intcode_cmp::{IntCodeComputer, RunState};
+use crate::intcode_error::IntcodeError;
+use crate::intcode_word::Word;
+
+/// Order, in which a node with several incoming connections reads their values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Merge {
+    /// Values are read in the order they arrive, no matter where they come from.
+    Arrival,
+    /// Values are read from the connections in turn, in the order the connections were made. The
+    /// node waits for a value on the connection next in turn, even if others have values.
+    RoundRobin,
+}
+
+/// Node waiting for input in a deadlocked circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedNode {
+    pub name: String,
+    /// Address of the input instruction the node is waiting at
+    pub i_pointer: usize,
+    /// Number of values received on other connections than the one next in turn (only with
+    /// `Merge::RoundRobin`)
+    pub queued: usize,
+}
+
+/// Error while building or running a circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    /// Two nodes have the same name.
+    DuplicateNode(String),
+    /// A connection or setting refers to a node, which does not exist.
+    UnknownNode(String),
+    /// The program of a node failed.
+    Intcode { node: String, error: IntcodeError },
+    /// Every node, which has not halted, waits for input, but no values are on their way. Lists
+    /// the waiting nodes.
+    Deadlock(Vec<BlockedNode>),
+    /// A node halted without the output value the caller expected, e.g. the final signal of an
+    /// amplifier.
+    NoSignal { node: String },
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::DuplicateNode(name) => write!(f, "duplicate node {}", name),
+            CircuitError::UnknownNode(name) => write!(f, "unknown node {}", name),
+            CircuitError::Intcode { node, error } => write!(f, "node {}: {}", node, error),
+            CircuitError::Deadlock(blocked) => {
+                write!(f, "deadlock:")?;
+                for (number, node) in blocked.iter().enumerate() {
+                    let separator = if number == 0 { " " } else { ", " };
+                    write!(
+                        f,
+                        "{}{} waits for input at {}",
+                        separator, node.name, node.i_pointer
+                    )?;
+                }
+                Ok(())
+            }
+            CircuitError::NoSignal { node } => write!(f, "node {} halted without a signal", node),
+        }
+    }
+}
+
+impl Error for CircuitError {}
+
+#[derive(Debug)]
+struct NodeSpec<W> {
+    name: String,
+    program: Vec<W>,
+    inputs: Vec<W>,
+    merge: Merge,
+}
+
+/// Builder of a `Circuit`.
+///
+/// Errors, like connections to unknown nodes, are reported by `build`.
+#[derive(Debug)]
+pub struct CircuitBuilder<W> {
+    nodes: Vec<NodeSpec<W>>,
+    connections: Vec<(String, String)>,
+    merges: Vec<(String, Merge)>,
+    loop_detection: bool,
+}
+
+impl<W: Word> CircuitBuilder<W> {
+    /// Add a node running its own copy of a program.
+    ///
+    /// * `name` - name of the node, unique in the circuit
+    /// * `program` - program instructions and data
+    /// * `inputs` - values read by the node before anything it receives from other nodes, e.g.
+    ///   the phase setting of an amplifier
+    pub fn node(&mut self, name: &str, program: &[W], inputs: &[W]) -> &mut CircuitBuilder<W> {
+        self.nodes.push(NodeSpec {
+            name: name.to_string(),
+            program: program.to_vec(),
+            inputs: inputs.to_vec(),
+            merge: Merge::Arrival,
+        });
+        self
+    }
+
+    /// Connect the output of a node to the input of another one, or of itself.
+    ///
+    /// A node connected to several nodes sends each of them every output value (fan-out).
+    pub fn connect(&mut self, from: &str, to: &str) -> &mut CircuitBuilder<W> {
+        self.connections.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Set how a node merges values from several incoming connections (default: `Arrival`).
+    pub fn merge(&mut self, name: &str, merge: Merge) -> &mut CircuitBuilder<W> {
+        self.merges.push((name.to_string(), merge));
+        self
+    }
+
+    /// Switch detection of infinite loops on or off for all nodes (default: off).
+    ///
+    /// A node looping forever without reading input, whether it outputs values or not, would
+    /// keep `Circuit::run` from ever returning. With detection, the run fails with
+    /// `IntcodeError::InfiniteLoop` instead (see `IntCodeComputer::set_loop_detection`).
+    pub fn loop_detection(&mut self, what: bool) -> &mut CircuitBuilder<W> {
+        self.loop_detection = what;
+        self
+    }
+
+    pub fn build(&self) -> Result<Circuit<W>, CircuitError> {
+        let mut indices = HashMap::new();
+        for (index, spec) in self.nodes.iter().enumerate() {
+            if indices.insert(spec.name.as_str(), index).is_some() {
+                return Err(CircuitError::DuplicateNode(spec.name.clone()));
+            }
+        }
+        let index = |name: &String| {
+            indices
+                .get(name.as_str())
+                .copied()
+                .ok_or_else(|| CircuitError::UnknownNode(name.clone()))
+        };
+
+        let mut nodes: Vec<Node<W>> = self
+            .nodes
+            .iter()
+            .map(|spec| {
+                let mut icc = IntCodeComputer::new(&spec.program);
+                icc.set_return_control(true);
+                icc.set_loop_detection(self.loop_detection);
+                icc.set_outputs_break_loops(false);
+                icc.input.extend(spec.inputs.iter().copied());
+                Node {
+                    name: spec.name.clone(),
+                    icc,
+                    merge: spec.merge,
+                    queues: Vec::new(),
+                    next_queue: 0,
+                    targets: Vec::new(),
+                    outputs: Vec::new(),
+                }
+            })
+            .collect();
+        for (name, merge) in &self.merges {
+            nodes[index(name)?].merge = *merge;
+        }
+        for (from, to) in &self.connections {
+            let (from, to) = (index(from)?, index(to)?);
+            let queue = nodes[to].queues.len();
+            nodes[to].queues.push(VecDeque::new());
+            nodes[from].targets.push((to, queue));
+        }
+        Ok(Circuit { nodes })
+    }
+}
+
+#[derive(Debug)]
+struct Node<W: Word> {
+    name: String,
+    icc: IntCodeComputer<W>,
+    merge: Merge,
+    /// Values received per incoming connection (only used with `Merge::RoundRobin`)
+    queues: Vec<VecDeque<W>>,
+    next_queue: usize,
+    /// Receivers of the output values, as node and queue index
+    targets: Vec<(usize, usize)>,
+    outputs: Vec<W>,
+}
+
+impl<W: Word> Node<W> {
+    fn receive(&mut self, queue: usize, value: W) {
+        match self.merge {
+            Merge::Arrival => self.icc.input.push_back(value),
+            Merge::RoundRobin => self.queues[queue].push_back(value),
+        }
+    }
+
+    /// Run until the node outputs a value, halts or waits for input, which has not arrived yet.
+    ///
+    /// Returns the output value, if any.
+    fn run(&mut self) -> Result<Option<W>, CircuitError> {
+        loop {
+            let state = self.icc.run().map_err(|error| CircuitError::Intcode {
+                node: self.name.clone(),
+                error,
+            })?;
+            match state {
+                RunState::Output(value) => return Ok(Some(value)),
+                RunState::Halted => return Ok(None),
+                RunState::AwaitingInput => {
+                    let queue = match self.queues.get_mut(self.next_queue) {
+                        Some(queue) if self.merge == Merge::RoundRobin => queue,
+                        _ => return Ok(None),
+                    };
+                    match queue.pop_front() {
+                        Some(value) => self.icc.input.push_back(value),
+                        None => return Ok(None),
+                    }
+                    self.next_queue = (self.next_queue + 1) % self.queues.len();
+                }
+            }
+        }
+    }
+}
+
+/// Network of Intcode computers, whose outputs are wired to the inputs of others.
+///
+/// Any directed graph is possible: chains, loops, fan-out and fan-in. The nodes are run one
+/// after another, in the order they were added, each until it halts or waits for input. Their
+/// output values are passed on as they are produced, and rounds follow until all nodes have
+/// halted.
+///
+/// ```
+/// use intcode::intcode_circuit::Circuit;
+///
+/// // output the input plus one
+/// let increment = [3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+///
+/// let mut circuit = Circuit::builder()
+///     .node("first", &increment, &[1])
+///     .node("second", &increment, &[])
+///     .node("third", &increment, &[])
+///     .connect("first", "second")
+///     .connect("second", "third")
+///     .build()
+///     .unwrap();
+/// circuit.run().unwrap();
+///
+/// assert_eq!(Some(4), circuit.last_output("third"));
+/// ```
+#[derive(Debug)]
+pub struct Circuit<W: Word = i64> {
+    nodes: Vec<Node<W>>,
+}
+
+impl<W: Word> Circuit<W> {
+    pub fn builder() -> CircuitBuilder<W> {
+        CircuitBuilder {
+            nodes: Vec::new(),
+            connections: Vec::new(),
+            merges: Vec::new(),
+            loop_detection: false,
+        }
+    }
+
+    fn node(&self, name: &str) -> Option<&Node<W>> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Run the circuit until all nodes have halted.
+    ///
+    /// Fails if a program fails, or with `CircuitError::Deadlock` if all nodes, which have not
+    /// halted, wait for input and none of them received any since the last round.
+    pub fn run(&mut self) -> Result<(), CircuitError> {
+        loop {
+            let mut progress = false;
+            for index in 0..self.nodes.len() {
+                let steps = self.nodes[index].icc.steps();
+                let targets = self.nodes[index].targets.clone();
+                // values are passed on as they are produced, so nothing piles up in between
+                while let Some(value) = self.nodes[index].run()? {
+                    for &(target, queue) in &targets {
+                        self.nodes[target].receive(queue, value);
+                    }
+                    self.nodes[index].outputs.push(value);
+                }
+                progress |= self.nodes[index].icc.steps() != steps;
+            }
+
+            if self.nodes.iter().all(|node| node.icc.finished()) {
+                return Ok(());
+            }
+            if !progress {
+                return Err(CircuitError::Deadlock(self.blocked()));
+            }
+        }
+    }
+
+    fn blocked(&self) -> Vec<BlockedNode> {
+        self.nodes
+            .iter()
+            .filter(|node| !node.icc.finished())
+            .map(|node| BlockedNode {
+                name: node.name.clone(),
+                i_pointer: node.icc.i_pointer(),
+                queued: node.queues.iter().map(|queue| queue.len()).sum(),
+            })
+            .collect()
+    }
+
+    /// All output values of a node so far, or `None` if there is no such node.
+    pub fn outputs(&self, name: &str) -> Option<&[W]> {
+        self.node(name).map(|node| &node.outputs[..])
+    }
+
+    /// The last output value of a node, e.g. the final signal of an amplifier.
+    pub fn last_output(&self, name: &str) -> Option<W> {
+        self.node(name)
+            .and_then(|node| node.outputs.last().copied())
+    }
+
+    /// The computer of a node, for inspection.
+    pub fn computer(&self, name: &str) -> Option<&IntCodeComputer<W>> {
+        self.node(name).map(|node| &node.icc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_circuit::{BlockedNode, Circuit, CircuitError, Merge};
+    use crate::intcode_error::IntcodeError;
+
+    // output the first input minus the second one
+    const SUBTRACT: [i64; 18] = [
+        3, 15, 3, 16, 1002, 16, -1, 16, 1, 15, 16, 17, 4, 17, 99, 0, 0, 0,
+    ];
+
+    // output the input plus one, as long as there is input
+    const INCREMENT: [i64; 12] = [3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0];
+
+    #[test]
+    fn fan_out() {
+        let mut circuit = Circuit::builder()
+            .node("source", &[104, 5, 99], &[])
+            .node("left", &INCREMENT, &[])
+            .node("right", &SUBTRACT, &[10])
+            .connect("source", "left")
+            .connect("source", "right")
+            .build()
+            .unwrap();
+        assert_eq!(
+            Err(CircuitError::Deadlock(vec![BlockedNode {
+                name: "left".to_string(),
+                i_pointer: 0,
+                queued: 0
+            }])),
+            circuit.run()
+        );
+        assert_eq!(Some(&[6][..]), circuit.outputs("left"));
+        assert_eq!(Some(5), circuit.last_output("right"));
+        assert!(circuit.computer("right").unwrap().finished());
+        assert_eq!(None, circuit.last_output("middle"));
+    }
+
+    #[test]
+    fn fan_in() {
+        let build = |merge| {
+            let mut builder = Circuit::builder();
+            builder
+                .node("one", &[104, 1, 99], &[])
+                .node("ten", &[104, 10, 99], &[])
+                .node("diff", &SUBTRACT, &[])
+                .connect("ten", "diff")
+                .connect("one", "diff")
+                .merge("diff", merge);
+            builder.build().unwrap()
+        };
+
+        let mut circuit = build(Merge::Arrival);
+        circuit.run().unwrap();
+        assert_eq!(Some(-9), circuit.last_output("diff"));
+
+        let mut circuit = build(Merge::RoundRobin);
+        circuit.run().unwrap();
+        assert_eq!(Some(9), circuit.last_output("diff"));
+    }
+
+    #[test]
+    fn feedback_loop() {
+        // count up to ten, passing the value around three nodes
+        let stop = [
+            3, 15, 1007, 15, 10, 16, 1006, 16, 14, 4, 15, 1105, 1, 0, 99, 0, 0,
+        ];
+        let mut circuit = Circuit::builder()
+            .node("a", &INCREMENT, &[0])
+            .node("b", &INCREMENT, &[])
+            .node("stop", &stop, &[])
+            .connect("a", "b")
+            .connect("b", "stop")
+            .connect("stop", "a")
+            .build()
+            .unwrap();
+        // the other nodes wait for input forever, once the last one halts
+        let err = circuit.run().unwrap_err();
+        assert_eq!(
+            "deadlock: a waits for input at 0, b waits for input at 0",
+            err.to_string()
+        );
+        assert_eq!(Some(&[1, 3, 5, 7, 9][..]), circuit.outputs("a"));
+        assert_eq!(Some(&[2, 4, 6, 8][..]), circuit.outputs("stop"));
+    }
+
+    #[test]
+    fn build_errors() {
+        let result = Circuit::builder()
+            .node("a", &INCREMENT, &[])
+            .node("a", &INCREMENT, &[])
+            .build();
+        assert_eq!(
+            Some(CircuitError::DuplicateNode("a".to_string())),
+            result.err()
+        );
+
+        let result = Circuit::builder()
+            .node("a", &INCREMENT, &[])
+            .connect("a", "b")
+            .build();
+        assert_eq!(
+            Some(CircuitError::UnknownNode("b".to_string())),
+            result.err()
+        );
+    }
+
+    #[test]
+    fn blocked_by_round_robin() {
+        // values from "one" are queued, while "diff" waits for "never"
+        let mut circuit = Circuit::builder()
+            .node("one", &[104, 1, 104, 2, 99], &[])
+            .node("never", &[3, 0, 99], &[])
+            .node("diff", &SUBTRACT, &[])
+            .connect("never", "diff")
+            .connect("one", "diff")
+            .merge("diff", Merge::RoundRobin)
+            .build()
+            .unwrap();
+        match circuit.run() {
+            Err(CircuitError::Deadlock(blocked)) => {
+                assert_eq!(2, blocked.len());
+                assert_eq!("diff", blocked[1].name);
+                assert_eq!(2, blocked[1].queued);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn endless_output() {
+        // outputs 1 forever without reading input, so the circuit never finishes
+        let mut circuit = Circuit::builder()
+            .node("source", &[104, 1, 1105, 1, 0], &[])
+            .node("sink", &[3, 0, 1105, 1, 0], &[])
+            .connect("source", "sink")
+            .loop_detection(true)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            circuit.run(),
+            Err(CircuitError::Intcode {
+                node,
+                error: IntcodeError::InfiniteLoop { period: 2, .. },
+            }) if node == "source"
+        ));
+    }
+
+    #[test]
+    fn infinite_loop_in_node() {
+        let mut circuit = Circuit::builder()
+            .node("spin", &[1105, 1, 0], &[])
+            .loop_detection(true)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            circuit.run(),
+            Err(CircuitError::Intcode {
+                error: IntcodeError::InfiniteLoop { .. },
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,1511 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::intcode_decode::{DecodeCache, Decoded};
+use crate::intcode_disasm::op_info;
+use crate::intcode_error::IntcodeError;
+use crate::intcode_io::{Input, InputDevice, OutputDevice};
+use crate::intcode_loop::LoopDetector;
+use crate::intcode_mem::Memory;
+use crate::intcode_opcodes::{CustomOp, OpcodeRegistry, Param};
+use crate::intcode_profile::{Accesses, Profile};
+use crate::intcode_snapshot::{Snapshot, SnapshotError};
+use crate::intcode_trace::{MemoryWrite, TraceEvent, Tracer};
+use crate::intcode_word::Word;
+
+/// Behaviour of arithmetic instructions if the result does not fit into a word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wrap around at the boundary of the word type (two's complement).
+    Wrapping,
+    /// Stop the computation with `IntcodeError::Overflow`.
+    Checked,
+    /// Panic, as unchecked arithmetic does in debug builds.
+    Panic,
+}
+
+/// Number of steps between two checks of the time budget
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// Reason why the computer returned control to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState<W> {
+    /// The program executed the halt instruction (99).
+    Halted,
+    /// The program needs input, but none is available (only with `return_control`).
+    AwaitingInput,
+    /// The program produced an output value (only returned by `step` and `run`).
+    Output(W),
+}
+
+/// Intcode computer implementation from AdventOfCode 2019 puzzles.
+///
+/// A detailed description is found in the puzzle descriptions:
+/// https://adventofcode.com/2019/day/2
+/// https://adventofcode.com/2019/day/5
+/// https://adventofcode.com/2019/day/7
+/// https://adventofcode.com/2019/day/9
+///
+/// The computer is generic over its word type `W` (`i32`, `i64` or `i128`), which defaults to
+/// `i64`, and over its input and output devices `I` and `O` (see `intcode_io`), which default to
+/// in-memory queues.
+///
+/// ```
+/// use intcode::intcode_cmp::IntCodeComputer;
+///
+/// let program = [3,3,1105,-1,9,1101,0,0,12,4,12,99,1];
+///
+/// let mut icc = IntCodeComputer::new(&program);
+/// icc.input.push_back(0);
+/// icc.compute().unwrap();
+/// let result = icc.output.pop_front().unwrap();
+///
+/// assert_eq!(0, result);
+/// ```
+///
+/// Any other combination of devices can be given with `with_io`, e.g. channels for computers
+/// running in their own threads:
+///
+/// ```
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use intcode::intcode_cmp::IntCodeComputer;
+///
+/// let program = [3,3,1105,-1,9,1101,0,0,12,4,12,99,1];
+///
+/// let (tx_in, rx_in) = mpsc::channel();
+/// let (tx_out, rx_out) = mpsc::channel();
+/// let mut icc = IntCodeComputer::with_io(&program, rx_in, tx_out);
+/// thread::spawn(move || icc.compute().unwrap());
+/// tx_in.send(0).unwrap();
+///
+/// assert_eq!(0, rx_out.recv().unwrap());
+/// ```
+///
+/// Alternatively, the computer can be driven as a coroutine with `run`, which hands over every
+/// output value directly instead of writing it to the output device:
+///
+/// ```
+/// use intcode::intcode_cmp::{IntCodeComputer, RunState};
+///
+/// let program = [3,3,1105,-1,9,1101,0,0,12,4,12,99,1];
+///
+/// let mut icc = IntCodeComputer::new(&program);
+/// icc.set_return_control(true);
+/// assert_eq!(RunState::AwaitingInput, icc.run().unwrap());
+/// icc.input.push_back(0);
+/// assert_eq!(RunState::Output(0), icc.run().unwrap());
+/// assert_eq!(RunState::Halted, icc.run().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct IntCodeComputer<W: Word = i64, I = VecDeque<W>, O = VecDeque<W>> {
+    pub input: I,
+    pub output: O,
+    memory: Memory<W>,
+    i_pointer: usize,
+    relative_base: W,
+    return_control: bool,
+    overflow: Overflow,
+    steps: u64,
+    max_steps: Option<u64>,
+    halted: bool,
+    trace: Option<Tracer>,
+    time_budget: Option<(Instant, Duration)>,
+    loop_detector: Option<LoopDetector<W>>,
+    /// Whether output resets the loop detector like input does
+    outputs_break_loops: bool,
+    decode_cache: Option<DecodeCache>,
+    profile: Option<Profile>,
+    opcodes: Option<OpcodeRegistry<W>>,
+    /// Instruction being executed
+    decoded: Decoded,
+    /// Address of the instruction, which produced the last output value
+    last_output_pointer: usize,
+}
+
+impl<W: Word> IntCodeComputer<W> {
+    /// Create a new Intcode computer with in-memory queues for input and output.
+    ///
+    /// Arithmetic overflow is checked by default, see `set_overflow`.
+    ///
+    /// * `program` - program instructions and data, loaded at address zero of an otherwise
+    ///   zero-initialised memory
+    pub fn new(program: &[W]) -> IntCodeComputer<W> {
+        IntCodeComputer::with_io(program, VecDeque::new(), VecDeque::new())
+    }
+
+    /// Create an Intcode computer from a snapshot, with the pending input in its input queue.
+    pub fn from_snapshot(snapshot: Snapshot<W>) -> IntCodeComputer<W> {
+        let mut icc = IntCodeComputer::new(&[]);
+        icc.input.extend(snapshot.input);
+        icc.memory = snapshot.memory;
+        icc.i_pointer = snapshot.i_pointer;
+        icc.relative_base = snapshot.relative_base;
+        icc.return_control = snapshot.return_control;
+        icc.overflow = snapshot.overflow;
+        icc.steps = snapshot.steps;
+        icc.max_steps = snapshot.max_steps;
+        icc.halted = snapshot.halted;
+        icc
+    }
+
+    /// Restore an Intcode computer saved with `save`.
+    ///
+    /// Fails if the snapshot is written in an unsupported version of the format, or by a
+    /// computer with another word type.
+    ///
+    /// * `reader` - snapshot file, see `Snapshot` for the format
+    pub fn restore<R: BufRead>(reader: R) -> Result<IntCodeComputer<W>, SnapshotError> {
+        Ok(IntCodeComputer::from_snapshot(Snapshot::read_from(reader)?))
+    }
+}
+
+impl<W: Word> IntCodeComputer<W, mpsc::Receiver<W>, mpsc::Sender<W>> {
+    /// Create a new Intcode computer connected to channels.
+    ///
+    /// Returns the computer, the sender for its input and the receiver for its output.
+    ///
+    /// * `program` - program instructions and data
+    #[allow(clippy::type_complexity)]
+    pub fn with_channels(
+        program: &[W],
+    ) -> (
+        IntCodeComputer<W, mpsc::Receiver<W>, mpsc::Sender<W>>,
+        mpsc::Sender<W>,
+        mpsc::Receiver<W>,
+    ) {
+        let (tx_in, rx_in) = mpsc::channel();
+        let (tx_out, rx_out) = mpsc::channel();
+        (
+            IntCodeComputer::with_io(program, rx_in, tx_out),
+            tx_in,
+            rx_out,
+        )
+    }
+}
+
+impl<W: Word, I: InputDevice<W>, O: OutputDevice<W>> IntCodeComputer<W, I, O> {
+    /// Create a new Intcode computer with the given input and output devices.
+    ///
+    /// * `program` - program instructions and data
+    /// * `input` - device to read input values from
+    /// * `output` - device to write output values to
+    pub fn with_io(program: &[W], input: I, output: O) -> IntCodeComputer<W, I, O> {
+        IntCodeComputer {
+            input,
+            output,
+            memory: Memory::new(program),
+            i_pointer: 0,
+            relative_base: W::default(),
+            return_control: false,
+            overflow: Overflow::Checked,
+            steps: 0,
+            max_steps: None,
+            halted: false,
+            trace: None,
+            time_budget: None,
+            loop_detector: None,
+            outputs_break_loops: true,
+            decode_cache: None,
+            profile: None,
+            opcodes: None,
+            decoded: Decoded::default(),
+            last_output_pointer: 0,
+        }
+    }
+
+    /// Set the `return_control option.
+    ///
+    /// * `what` - let computer return control to caller, if no input is available, instead of
+    ///   waiting for the input device. Devices, which cannot wait (e.g. queues), always return
+    ///   control.
+    pub fn set_return_control(&mut self, what: bool) {
+        self.return_control = what;
+    }
+
+    /// Set the behaviour of arithmetic instructions on overflow.
+    ///
+    /// * `what` - wrap around, return an error or panic
+    pub fn set_overflow(&mut self, what: Overflow) {
+        self.overflow = what;
+    }
+
+    /// Set the maximum number of instructions to execute.
+    ///
+    /// * `what` - `compute` fails with `IntcodeError::StepLimitExceeded` once the number of
+    ///   executed instructions reaches this limit; `None` for no limit
+    pub fn set_max_steps(&mut self, what: Option<u64>) {
+        self.max_steps = what;
+    }
+
+    /// Record every executed instruction, which passes the filter of the tracer.
+    ///
+    /// * `what` - tracer writing the trace; `None` to stop tracing
+    pub fn set_trace(&mut self, what: Option<Tracer>) {
+        self.trace = what;
+    }
+
+    /// Stop tracing and hand back the tracer, e.g. to `finish` it.
+    pub fn take_trace(&mut self) -> Option<Tracer> {
+        self.trace.take()
+    }
+
+    /// Set the maximum wall-clock time to spend on the computation, starting now.
+    ///
+    /// * `what` - `compute` fails with `IntcodeError::TimeBudgetExceeded` once the time is up;
+    ///   `None` for no limit. The clock is checked every 1024 instructions only, and keeps running
+    ///   while the computer waits for input or the caller has control.
+    pub fn set_time_budget(&mut self, what: Option<Duration>) {
+        self.time_budget = what.map(|budget| (Instant::now(), budget));
+    }
+
+    /// Switch detection of infinite loops on or off.
+    ///
+    /// * `what` - fail with `IntcodeError::InfiniteLoop` if the machine returns to a previous
+    ///   state (same instruction pointer, relative base and memory) without any I/O in between.
+    ///   Such a program can never halt. Detection costs a few comparisons per instruction, and a
+    ///   hash update per memory write.
+    pub fn set_loop_detection(&mut self, what: bool) {
+        self.loop_detector = if what {
+            Some(LoopDetector::new(&self.memory))
+        } else {
+            None
+        };
+    }
+
+    /// Set whether output counts as I/O for the loop detection (default: on).
+    ///
+    /// The nodes of a `Circuit` switch it off, as a node, which outputs forever without reading
+    /// input, never halts either, and keeps the circuit from finishing.
+    pub(crate) fn set_outputs_break_loops(&mut self, what: bool) {
+        self.outputs_break_loops = what;
+    }
+
+    /// Switch caching of decoded instructions on or off.
+    ///
+    /// * `what` - decode every instruction once, instead of on every execution. Speeds up
+    ///   programs, which spend their time in loops, at the cost of a few bytes per address of
+    ///   code. Writes to the memory invalidate the decoded instruction at the written address, so
+    ///   self-modifying programs are safe.
+    pub fn set_decode_cache(&mut self, what: bool) {
+        self.decode_cache = if what {
+            Some(DecodeCache::default())
+        } else {
+            None
+        };
+    }
+
+    /// Switch profiling on or off.
+    ///
+    /// * `what` - count executions per address and opcode, memory accesses per cell and taken
+    ///   jumps backwards (i.e. loop iterations) from now on, see `Profile`; `false` drops the
+    ///   profile recorded so far
+    pub fn set_profiling(&mut self, what: bool) {
+        self.profile = if what { Some(Profile::default()) } else { None };
+    }
+
+    /// Set the custom instructions, see `OpcodeRegistry`.
+    ///
+    /// * `what` - instructions executed in addition to, or instead of, the built-in ones. They
+    ///   are kept by forks, but not by snapshots, as their handlers cannot be saved. Custom
+    ///   instructions are profiled, but not traced.
+    pub fn set_opcodes(&mut self, what: Option<OpcodeRegistry<W>>) {
+        self.opcodes = what;
+    }
+
+    /// Profile recorded since profiling was switched on.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Stop profiling and hand back the profile.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Whether the program has executed the halt instruction.
+    pub fn finished(&self) -> bool {
+        self.halted
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn i_pointer(&self) -> usize {
+        self.i_pointer
+    }
+
+    /// Current relative base.
+    pub fn relative_base(&self) -> W {
+        self.relative_base
+    }
+
+    /// Memory of the computer, for inspection.
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    /// Overwrite a memory cell, e.g. to patch a program while debugging.
+    pub fn patch_memory(&mut self, address: usize, value: W) {
+        self.write(address, value);
+        if let Some(detector) = &mut self.loop_detector {
+            detector.reset();
+        }
+    }
+
+    fn write(&mut self, address: usize, value: W) {
+        if let Some(detector) = &mut self.loop_detector {
+            detector.write(address, self.memory.read(address), value);
+        }
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(address);
+        }
+        self.memory.write(address, value);
+    }
+
+    /// Create an independent copy of the computer, with fresh, empty queues for input and output.
+    ///
+    /// Memory and registers are identical, so the copy continues where the computer stands, e.g.
+    /// at an input instruction. Memory pages are shared copy-on-write, which keeps forks of large
+    /// programs cheap. Pending input of the original, and its trace, are not copied.
+    ///
+    /// ```
+    /// use intcode::intcode_cmp::{IntCodeComputer, RunState};
+    ///
+    /// // output twice the input
+    /// let program = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+    ///
+    /// let mut icc = IntCodeComputer::new(&program[..]);
+    /// icc.set_return_control(true);
+    /// assert_eq!(RunState::AwaitingInput, icc.run().unwrap());
+    /// for input in 1..4 {
+    ///     let mut fork = icc.fork();
+    ///     fork.input.push_back(input);
+    ///     assert_eq!(RunState::Output(2 * input), fork.run().unwrap());
+    /// }
+    /// ```
+    pub fn fork(&self) -> IntCodeComputer<W> {
+        self.fork_with_io(VecDeque::new(), VecDeque::new())
+    }
+
+    /// Create an independent copy of the computer, like `fork`, with the given I/O devices.
+    ///
+    /// * `input` - device to read input values from
+    /// * `output` - device to write output values to
+    pub fn fork_with_io<I2, O2>(&self, input: I2, output: O2) -> IntCodeComputer<W, I2, O2>
+    where
+        I2: InputDevice<W>,
+        O2: OutputDevice<W>,
+    {
+        IntCodeComputer {
+            input,
+            output,
+            memory: self.memory.clone(),
+            i_pointer: self.i_pointer,
+            relative_base: self.relative_base,
+            return_control: self.return_control,
+            overflow: self.overflow,
+            steps: self.steps,
+            max_steps: self.max_steps,
+            halted: self.halted,
+            trace: None,
+            time_budget: self.time_budget,
+            loop_detector: self.loop_detector.clone(),
+            outputs_break_loops: self.outputs_break_loops,
+            decode_cache: self.decode_cache.clone(),
+            profile: None,
+            opcodes: self.opcodes.clone(),
+            decoded: self.decoded,
+            last_output_pointer: self.last_output_pointer,
+        }
+    }
+
+    /// Capture the state of the computer, including the input pending in the input device.
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.memory.clone(),
+            i_pointer: self.i_pointer,
+            relative_base: self.relative_base,
+            return_control: self.return_control,
+            overflow: self.overflow,
+            steps: self.steps,
+            max_steps: self.max_steps,
+            halted: self.halted,
+            input: self.input.pending(),
+        }
+    }
+
+    /// Save the state of the computer, so it can be resumed later with `restore`.
+    ///
+    /// * `writer` - destination of the snapshot, e.g. a file
+    pub fn save<Wr: Write>(&self, writer: Wr) -> io::Result<()> {
+        self.snapshot().write_to(writer)
+    }
+
+    fn instruction(&self) -> i128 {
+        self.memory.read(self.i_pointer).to_i128()
+    }
+
+    /// Convert a value to an address, which must not be negative.
+    fn address(&self, value: i128) -> Result<usize, IntcodeError> {
+        if value < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                i_pointer: self.i_pointer,
+                instruction: self.instruction(),
+                address: value,
+            });
+        }
+        usize::try_from(value).map_err(|_| IntcodeError::AddressOutOfRange {
+            i_pointer: self.i_pointer,
+            instruction: self.instruction(),
+            address: value,
+        })
+    }
+
+    /// Advance the instruction pointer behind the current instruction.
+    ///
+    /// * `size` - number of words of the instruction
+    fn advance(&mut self, size: usize) -> Result<(), IntcodeError> {
+        self.i_pointer = self.address(self.i_pointer as i128 + size as i128)?;
+        Ok(())
+    }
+
+    fn fetch_param(&self, number: usize) -> Result<W, IntcodeError> {
+        let address = self.address(self.i_pointer as i128 + 1 + number as i128)?;
+        Ok(self.memory.read(address))
+    }
+
+    fn fetch_mode(&self, number: usize) -> u8 {
+        self.decoded.modes[number]
+    }
+
+    fn invalid_mode(&self, number: usize) -> IntcodeError {
+        IntcodeError::InvalidMode {
+            i_pointer: self.i_pointer,
+            instruction: self.instruction(),
+            param: number,
+            mode: self.fetch_mode(number) as i128,
+        }
+    }
+
+    fn fetch_arg(&self, number: usize) -> Result<W, IntcodeError> {
+        let param = self.fetch_param(number)?;
+        match self.fetch_mode(number) {
+            // position mode
+            0 => Ok(self.memory.read(self.address(param.to_i128())?)),
+
+            // immediate mode
+            1 => Ok(param),
+
+            // relative mode
+            2 => {
+                let address = self.relative_base.to_i128() + param.to_i128();
+                Ok(self.memory.read(self.address(address)?))
+            }
+
+            _ => Err(self.invalid_mode(number)),
+        }
+    }
+
+    /// Resolve the address an instruction writes to. Writing in immediate mode is invalid.
+    fn fetch_target(&self, number: usize) -> Result<usize, IntcodeError> {
+        let param = self.fetch_param(number)?.to_i128();
+        match self.fetch_mode(number) {
+            // position mode
+            0 => self.address(param),
+
+            // relative mode
+            2 => self.address(self.relative_base.to_i128() + param),
+
+            // immediate mode
+            1 => Err(IntcodeError::WriteInImmediateMode {
+                i_pointer: self.i_pointer,
+                instruction: self.instruction(),
+                param: number,
+            }),
+
+            _ => Err(self.invalid_mode(number)),
+        }
+    }
+
+    fn fetch_arg_0_1_t(&mut self) -> Result<(W, W, usize), IntcodeError> {
+        let a = self.fetch_arg(0)?;
+        let b = self.fetch_arg(1)?;
+        let target = self.fetch_target(2)?;
+
+        Ok((a, b, target))
+    }
+
+    /// Apply the overflow policy to the result of a checked operation.
+    fn handle_overflow<F>(&self, checked: Option<W>, wrapping: F) -> Result<W, IntcodeError>
+    where
+        F: FnOnce() -> W,
+    {
+        match (checked, self.overflow) {
+            (Some(result), _) => Ok(result),
+            (None, Overflow::Wrapping) => Ok(wrapping()),
+            (None, Overflow::Checked) => Err(IntcodeError::Overflow {
+                i_pointer: self.i_pointer,
+                instruction: self.instruction(),
+            }),
+            (None, Overflow::Panic) => {
+                panic!("OVERFLOW AT {}: {}", self.i_pointer, self.instruction());
+            }
+        }
+    }
+
+    fn add(&self, a: W, b: W) -> Result<W, IntcodeError> {
+        self.handle_overflow(a.checked_add(b), || a.wrapping_add(b))
+    }
+
+    fn mul(&self, a: W, b: W) -> Result<W, IntcodeError> {
+        self.handle_overflow(a.checked_mul(b), || a.wrapping_mul(b))
+    }
+
+    fn handle_math_instr<F>(&mut self, func: F) -> Result<Option<RunState<W>>, IntcodeError>
+    where
+        F: Fn(&Self, W, W) -> Result<W, IntcodeError>,
+    {
+        let (a, b, target) = self.fetch_arg_0_1_t()?;
+        let result = func(self, a, b)?;
+        self.write(target, result);
+        self.advance(4)?;
+        Ok(None)
+    }
+
+    fn fetch_input(&mut self) -> Result<bool, IntcodeError> {
+        let target = self.fetch_target(0)?;
+        let input_int = match self.input.read(!self.return_control) {
+            Input::Value(input_int) => input_int,
+            Input::Empty => return Ok(false),
+            Input::Disconnected => {
+                return Err(IntcodeError::InputDisconnected {
+                    i_pointer: self.i_pointer,
+                    instruction: self.instruction(),
+                })
+            }
+        };
+        self.write(target, input_int);
+        self.advance(2)?;
+        Ok(true)
+    }
+
+    fn fetch_output(&mut self) -> Result<W, IntcodeError> {
+        let a = self.fetch_arg(0)?;
+        self.advance(2)?;
+        Ok(a)
+    }
+
+    fn jump_if(&mut self, what: bool) -> Result<Option<RunState<W>>, IntcodeError> {
+        let a = self.fetch_arg(0)?;
+        let b = self.fetch_arg(1)?;
+        if (a != W::default()) == what {
+            self.i_pointer = self.address(b.to_i128())?;
+        } else {
+            self.advance(3)?;
+        }
+        Ok(None)
+    }
+
+    fn adjust_relative_base(&mut self) -> Result<Option<RunState<W>>, IntcodeError> {
+        let a = self.fetch_arg(0)?;
+        self.relative_base = self.add(self.relative_base, a)?;
+        self.advance(2)?;
+        Ok(None)
+    }
+
+    /// Memory accesses of the next instruction, as far as its operands are valid.
+    ///
+    /// * `params` - role of every parameter
+    fn accesses<P: Iterator<Item = Param>>(&self, params: P) -> Accesses {
+        let address = |number: usize| {
+            let param = self.fetch_param(number).ok()?.to_i128();
+            match self.fetch_mode(number) {
+                0 => self.address(param).ok(),
+                2 => self.address(self.relative_base.to_i128() + param).ok(),
+                _ => None,
+            }
+        };
+        let mut accesses = Accesses::default();
+        for (number, param) in params.enumerate() {
+            match param {
+                Param::Read => accesses.reads.extend(address(number)),
+                Param::Write => accesses.write = address(number),
+            }
+        }
+        accesses
+    }
+
+    /// Execute a custom instruction: resolve its parameters, call its handler and apply the
+    /// effect.
+    fn custom_instr(&mut self, op: &CustomOp<W>) -> Result<Option<RunState<W>>, IntcodeError> {
+        let mut args = Vec::with_capacity(op.params.len());
+        let mut targets = Vec::with_capacity(op.params.len());
+        for (number, param) in op.params.iter().enumerate() {
+            match param {
+                Param::Read => args.push(self.fetch_arg(number)?),
+                Param::Write => targets.push(self.fetch_target(number)?),
+            }
+        }
+        let failed = |message: String| IntcodeError::CustomInstructionFailed {
+            i_pointer: self.i_pointer,
+            instruction: self.instruction(),
+            message,
+        };
+        let effect = op.call(&args).map_err(failed)?;
+        if effect.writes.len() != targets.len() {
+            return Err(failed(format!(
+                "{} returned {} values for {} parameters written to",
+                op.mnemonic,
+                effect.writes.len(),
+                targets.len()
+            )));
+        }
+        for (&target, &value) in targets.iter().zip(&effect.writes) {
+            self.write(target, value);
+        }
+        match effect.jump {
+            Some(address) => self.i_pointer = address,
+            None => self.advance(1 + op.params.len())?,
+        }
+        // an output before halting is handed over first, the next step reports the halt
+        self.halted = effect.halt;
+        match (effect.output, effect.halt) {
+            (Some(value), _) => Ok(Some(RunState::Output(value))),
+            (None, true) => Ok(Some(RunState::Halted)),
+            (None, false) => Ok(None),
+        }
+    }
+
+    /// Start the trace event for the next instruction, if it is traced.
+    ///
+    /// Invalid instructions are not traced, as their execution fails anyway.
+    fn begin_trace(&self) -> Option<TraceEvent<W>> {
+        let tracer = self.trace.as_ref()?;
+        let instruction = self.instruction();
+        if !tracer.filter().matches(self.i_pointer, instruction % 100) {
+            return None;
+        }
+        let op = op_info(instruction % 100)?;
+        let reads = op.params - op.writes as usize;
+        let operands = (0..reads)
+            .map(|number| self.fetch_arg(number))
+            .collect::<Result<Vec<W>, IntcodeError>>()
+            .ok()?;
+        let mut writes = Vec::new();
+        if op.writes {
+            let address = self.fetch_target(reads).ok()?;
+            let old = self.memory.read(address);
+            writes.push(MemoryWrite {
+                address,
+                old,
+                new: old,
+            });
+        }
+        Some(TraceEvent {
+            step: self.steps,
+            i_pointer: self.i_pointer,
+            instruction,
+            operands,
+            writes,
+            jump: None,
+            input: None,
+            output: None,
+        })
+    }
+
+    /// Complete the trace event with the effects of the executed instruction and record it.
+    fn end_trace(&mut self, mut event: TraceEvent<W>, state: Option<RunState<W>>) {
+        for write in &mut event.writes {
+            write.new = self.memory.read(write.address);
+        }
+        match event.instruction % 100 {
+            3 => event.input = event.writes.first().map(|write| write.new),
+            5 | 6 => {
+                let taken = (event.operands[0] != W::default()) == (event.instruction % 100 == 5);
+                if taken {
+                    event.jump = Some(self.i_pointer);
+                }
+            }
+            _ => {}
+        }
+        if let Some(RunState::Output(value)) = state {
+            event.output = Some(value);
+        }
+        if let Some(tracer) = &mut self.trace {
+            tracer.record(&event);
+        }
+    }
+
+    /// Execute a single instruction.
+    ///
+    /// Returns `None` if the instruction has no effect visible to the caller, or else the
+    /// `RunState` it produced. Output values are returned as `RunState::Output`, and are not
+    /// written to the output device. If no input is available, or the
+    /// program has halted before, the instruction pointer is left unchanged.
+    pub fn step(&mut self) -> Result<Option<RunState<W>>, IntcodeError> {
+        if self.halted {
+            return Ok(Some(RunState::Halted));
+        }
+        if let Some(max_steps) = self.max_steps {
+            if self.steps >= max_steps {
+                return Err(IntcodeError::StepLimitExceeded {
+                    i_pointer: self.i_pointer,
+                    instruction: self.instruction(),
+                    steps: self.steps,
+                });
+            }
+        }
+        if let Some((start, budget)) = self.time_budget {
+            if self.steps.is_multiple_of(TIME_CHECK_INTERVAL) && start.elapsed() >= budget {
+                return Err(IntcodeError::TimeBudgetExceeded {
+                    i_pointer: self.i_pointer,
+                    instruction: self.instruction(),
+                    budget,
+                });
+            }
+        }
+        let decoded = match &mut self.decode_cache {
+            Some(cache) => cache.get(self.i_pointer, &self.memory),
+            None => Decoded::new(self.instruction()),
+        };
+        self.decoded = match decoded {
+            Some(decoded) => decoded,
+            None => {
+                return Err(IntcodeError::UnknownOpcode {
+                    i_pointer: self.i_pointer,
+                    instruction: self.instruction(),
+                });
+            }
+        };
+        let opcode = self.decoded.opcode;
+        let i_pointer = self.i_pointer;
+        let custom = self
+            .opcodes
+            .as_ref()
+            .and_then(|opcodes| opcodes.get_shared(opcode));
+        let event = match custom {
+            Some(_) => None,
+            None => self.begin_trace(),
+        };
+        let accesses = match (&self.profile, &custom, op_info(opcode as i128)) {
+            (None, _, _) => None,
+            (Some(_), Some(op), _) => Some(self.accesses(op.params.iter().copied())),
+            (Some(_), None, Some(op)) => {
+                let reads = op.params - op.writes as usize;
+                let params = (0..op.params).map(|number| {
+                    if number < reads {
+                        Param::Read
+                    } else {
+                        Param::Write
+                    }
+                });
+                Some(self.accesses(params))
+            }
+            (Some(_), None, None) => None,
+        };
+        let state = if let Some(op) = &custom {
+            self.custom_instr(op)?
+        } else {
+            match opcode {
+                1 => self.handle_math_instr(Self::add)?,
+                2 => self.handle_math_instr(Self::mul)?,
+                3 => {
+                    if !self.fetch_input()? {
+                        // if no input is present, control is returned to caller
+                        return Ok(Some(RunState::AwaitingInput));
+                    }
+                    None
+                }
+                4 => Some(RunState::Output(self.fetch_output()?)),
+                5 => self.jump_if(true)?,
+                6 => self.jump_if(false)?,
+                7 => self.handle_math_instr(|_, a, b| Ok(W::from((a < b) as i32)))?,
+                8 => self.handle_math_instr(|_, a, b| Ok(W::from((a == b) as i32)))?,
+                9 => self.adjust_relative_base()?,
+                99 => {
+                    self.halted = true;
+                    Some(RunState::Halted)
+                }
+                _ => {
+                    return Err(IntcodeError::UnknownOpcode {
+                        i_pointer: self.i_pointer,
+                        instruction: self.instruction(),
+                    });
+                }
+            }
+        };
+        if let Some(RunState::Output(_)) = state {
+            self.last_output_pointer = i_pointer;
+        }
+        if let Some(event) = event {
+            self.end_trace(event, state);
+        }
+        if let (Some(profile), Some(accesses)) = (&mut self.profile, accesses) {
+            let mnemonic = custom.as_ref().map(|op| op.mnemonic.as_str());
+            profile.record(
+                i_pointer,
+                opcode as i128,
+                mnemonic,
+                accesses,
+                self.i_pointer,
+            );
+        }
+        self.steps += 1;
+        if let Some(detector) = &mut self.loop_detector {
+            // the halt instruction leaves the state unchanged, but does not loop, and custom
+            // instructions may interact with the host like I/O does
+            if opcode == 3
+                || (opcode == 4 && self.outputs_break_loops)
+                || self.halted
+                || custom.is_some()
+            {
+                detector.reset();
+            } else if let Some(period) =
+                detector.check(self.i_pointer, self.relative_base, &self.memory)
+            {
+                return Err(IntcodeError::InfiniteLoop {
+                    i_pointer: self.i_pointer,
+                    instruction: self.instruction(),
+                    period,
+                });
+            }
+        }
+        Ok(state)
+    }
+
+    /// Run the program until it produces an output, needs input or halts.
+    ///
+    /// Like `step`, this hands over output values directly, so the computer can be driven as a
+    /// coroutine without an output device.
+    pub fn run(&mut self) -> Result<RunState<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Run a program on the computer.
+    ///
+    /// If the program expects an input, it is read from the input device. If no input is found,
+    /// either the computer will wait until input is provided (if `return_control` was initialized
+    /// to `false` and the device can wait), or else control is returned to the caller. In the
+    /// latter case, the `compute` method may be called again after input is supplied, in order to
+    /// start off at the same point, where it was left. Output values are written to the output
+    /// device.
+    ///
+    /// Returns why the computation stopped (`Halted` or `AwaitingInput`), or an `IntcodeError` if
+    /// the program is invalid, an I/O device is disconnected or a configured limit is hit.
+    pub fn compute(&mut self) -> Result<RunState<W>, IntcodeError> {
+        loop {
+            match self.run()? {
+                RunState::Output(value) => {
+                    if self.output.write(value).is_err() {
+                        let i_pointer = self.last_output_pointer;
+                        return Err(IntcodeError::OutputDisconnected {
+                            i_pointer,
+                            instruction: self.memory.read(i_pointer).to_i128(),
+                        });
+                    }
+                }
+                state => return Ok(state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::intcode_cmp::{IntCodeComputer, Overflow, RunState};
+    use crate::intcode_error::IntcodeError;
+    use crate::intcode_io::{FnInput, Recorder};
+
+    // input as taken from https://adventofcode.com/2019/day/5
+    pub const INTCODE_TEST_PROGRAM: [i32; 678] = [
+        3, 225, 1, 225, 6, 6, 1100, 1, 238, 225, 104, 0, 1002, 114, 19, 224, 1001, 224, -646, 224,
+        4, 224, 102, 8, 223, 223, 1001, 224, 7, 224, 1, 223, 224, 223, 1101, 40, 62, 225, 1101, 60,
+        38, 225, 1101, 30, 29, 225, 2, 195, 148, 224, 1001, 224, -40, 224, 4, 224, 1002, 223, 8,
+        223, 101, 2, 224, 224, 1, 224, 223, 223, 1001, 143, 40, 224, 101, -125, 224, 224, 4, 224,
+        1002, 223, 8, 223, 1001, 224, 3, 224, 1, 224, 223, 223, 101, 29, 139, 224, 1001, 224, -99,
+        224, 4, 224, 1002, 223, 8, 223, 1001, 224, 2, 224, 1, 224, 223, 223, 1101, 14, 34, 225,
+        102, 57, 39, 224, 101, -3420, 224, 224, 4, 224, 102, 8, 223, 223, 1001, 224, 7, 224, 1,
+        223, 224, 223, 1101, 70, 40, 225, 1102, 85, 69, 225, 1102, 94, 5, 225, 1, 36, 43, 224, 101,
+        -92, 224, 224, 4, 224, 1002, 223, 8, 223, 101, 1, 224, 224, 1, 224, 223, 223, 1102, 94, 24,
+        224, 1001, 224, -2256, 224, 4, 224, 102, 8, 223, 223, 1001, 224, 1, 224, 1, 223, 224, 223,
+        1102, 8, 13, 225, 1101, 36, 65, 224, 1001, 224, -101, 224, 4, 224, 102, 8, 223, 223, 101,
+        3, 224, 224, 1, 223, 224, 223, 4, 223, 99, 0, 0, 0, 677, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        1105, 0, 99999, 1105, 227, 247, 1105, 1, 99999, 1005, 227, 99999, 1005, 0, 256, 1105, 1,
+        99999, 1106, 227, 99999, 1106, 0, 265, 1105, 1, 99999, 1006, 0, 99999, 1006, 227, 274,
+        1105, 1, 99999, 1105, 1, 280, 1105, 1, 99999, 1, 225, 225, 225, 1101, 294, 0, 0, 105, 1, 0,
+        1105, 1, 99999, 1106, 0, 300, 1105, 1, 99999, 1, 225, 225, 225, 1101, 314, 0, 0, 106, 0, 0,
+        1105, 1, 99999, 8, 677, 226, 224, 1002, 223, 2, 223, 1006, 224, 329, 1001, 223, 1, 223,
+        1108, 226, 226, 224, 1002, 223, 2, 223, 1005, 224, 344, 101, 1, 223, 223, 1108, 226, 677,
+        224, 1002, 223, 2, 223, 1006, 224, 359, 101, 1, 223, 223, 107, 226, 226, 224, 1002, 223, 2,
+        223, 1005, 224, 374, 101, 1, 223, 223, 1107, 226, 226, 224, 1002, 223, 2, 223, 1005, 224,
+        389, 101, 1, 223, 223, 107, 677, 677, 224, 102, 2, 223, 223, 1006, 224, 404, 101, 1, 223,
+        223, 1008, 226, 226, 224, 1002, 223, 2, 223, 1006, 224, 419, 101, 1, 223, 223, 108, 677,
+        226, 224, 1002, 223, 2, 223, 1006, 224, 434, 101, 1, 223, 223, 1108, 677, 226, 224, 102, 2,
+        223, 223, 1005, 224, 449, 101, 1, 223, 223, 1008, 677, 226, 224, 102, 2, 223, 223, 1006,
+        224, 464, 1001, 223, 1, 223, 108, 677, 677, 224, 102, 2, 223, 223, 1005, 224, 479, 101, 1,
+        223, 223, 7, 677, 677, 224, 102, 2, 223, 223, 1005, 224, 494, 1001, 223, 1, 223, 8, 226,
+        677, 224, 102, 2, 223, 223, 1006, 224, 509, 101, 1, 223, 223, 107, 677, 226, 224, 1002,
+        223, 2, 223, 1005, 224, 524, 1001, 223, 1, 223, 7, 677, 226, 224, 1002, 223, 2, 223, 1005,
+        224, 539, 1001, 223, 1, 223, 1007, 226, 677, 224, 1002, 223, 2, 223, 1005, 224, 554, 1001,
+        223, 1, 223, 8, 677, 677, 224, 102, 2, 223, 223, 1006, 224, 569, 101, 1, 223, 223, 7, 226,
+        677, 224, 102, 2, 223, 223, 1006, 224, 584, 1001, 223, 1, 223, 1008, 677, 677, 224, 102, 2,
+        223, 223, 1005, 224, 599, 101, 1, 223, 223, 1007, 677, 677, 224, 1002, 223, 2, 223, 1006,
+        224, 614, 101, 1, 223, 223, 1107, 677, 226, 224, 1002, 223, 2, 223, 1006, 224, 629, 101, 1,
+        223, 223, 1107, 226, 677, 224, 1002, 223, 2, 223, 1006, 224, 644, 101, 1, 223, 223, 1007,
+        226, 226, 224, 102, 2, 223, 223, 1005, 224, 659, 1001, 223, 1, 223, 108, 226, 226, 224,
+        102, 2, 223, 223, 1006, 224, 674, 101, 1, 223, 223, 4, 223, 99, 226,
+    ];
+
+    #[test]
+    fn instruction_codes_01_02_03_04() {
+        let mut icc = IntCodeComputer::new(&INTCODE_TEST_PROGRAM);
+
+        // Functionality of the computer is being tested by the test program. Every successful run
+        // emits a zero at the end. The last output is the result for the first task in the
+        // challenge.
+        // https://adventofcode.com/2019/day/5
+        icc.input.push_back(1);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+        assert_eq!(15314507, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_05() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(0);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(-4);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(120);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_06() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(0);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(-4);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(120);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_07() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(0);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(-4);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(120);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_08() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 3, 1108, -1, 8, 3, 4, 3, 99];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(0);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(8);
+        icc.compute().unwrap();
+        assert_eq!(1, icc.output.pop_front().unwrap());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(120);
+        icc.compute().unwrap();
+        assert_eq!(0, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_09() {
+        // test program taken from https://adventofcode.com/2019/day/9
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        let result: Vec<i32> = icc.output.into_iter().collect();
+        assert_eq!(program.to_vec(), result);
+    }
+
+    #[test]
+    fn instruction_code_09_relative_targets() {
+        // relative mode for input (203) and math (21101) targets: echo the input, then output
+        // the sum of two immediates written via the relative base
+        let program = [109, 13, 203, 0, 204, 0, 21101, 3, 4, 1, 204, 1, 99, 0, 0];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.input.push_back(42);
+        icc.compute().unwrap();
+        assert_eq!(42, icc.output.pop_front().unwrap());
+        assert_eq!(7, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn instruction_code_09_large_numbers() {
+        // test programs taken from https://adventofcode.com/2019/day/9
+        let program: [i64; 8] = [1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(1219070632396864, icc.output.pop_front().unwrap());
+
+        let program: [i64; 3] = [104, 1125899906842624, 99];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(1125899906842624, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn i128_words() {
+        let program: [i128; 8] = [1102, 1 << 62, 1 << 62, 7, 4, 7, 99, 0];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(1 << 124, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn end_of_address_space() {
+        // the parameters of a jump written to the last two words lie beyond the address space
+        let last = usize::MAX as i128;
+        let program: [i128; 8] = [1101, 1105, 0, last - 1, 1105, 1, last - 1, 99];
+        let mut icc = IntCodeComputer::new(&program);
+        assert_eq!(
+            Err(IntcodeError::AddressOutOfRange {
+                i_pointer: usize::MAX - 1,
+                instruction: 1105,
+                address: last + 1
+            }),
+            icc.compute()
+        );
+
+        // an output in the last two words has no next instruction
+        let program: [i128; 8] = [1101, 104, 0, last - 1, 1105, 1, last - 1, 99];
+        let mut icc = IntCodeComputer::new(&program);
+        assert_eq!(
+            Err(IntcodeError::AddressOutOfRange {
+                i_pointer: usize::MAX - 1,
+                instruction: 104,
+                address: last + 1
+            }),
+            icc.compute()
+        );
+    }
+
+    // multiplies 1_000_000_000 by 3, which does not fit into an i32
+    const OVERFLOW_PROGRAM: [i32; 8] = [1002, 7, 3, 7, 4, 7, 99, 1_000_000_000];
+
+    #[test]
+    fn overflow_checked() {
+        let mut icc = IntCodeComputer::new(&OVERFLOW_PROGRAM);
+        assert_eq!(
+            Err(IntcodeError::Overflow {
+                i_pointer: 0,
+                instruction: 1002
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn overflow_wrapping() {
+        let mut icc = IntCodeComputer::new(&OVERFLOW_PROGRAM);
+        icc.set_overflow(Overflow::Wrapping);
+        icc.compute().unwrap();
+        assert_eq!(
+            1_000_000_000_i32.wrapping_mul(3),
+            icc.output.pop_front().unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "OVERFLOW AT 0: 1002")]
+    fn overflow_panic() {
+        let mut icc = IntCodeComputer::new(&OVERFLOW_PROGRAM);
+        icc.set_overflow(Overflow::Panic);
+        icc.compute().unwrap();
+    }
+
+    #[test]
+    fn memory_beyond_program() {
+        // write to a far address and read it back, then read a cell never written
+        let program = [
+            1101,
+            5,
+            6,
+            1_000_000_000,
+            4,
+            1_000_000_000,
+            4,
+            2_000_000_000,
+            99,
+        ];
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(11, icc.output.pop_front().unwrap());
+        assert_eq!(0, icc.output.pop_front().unwrap());
+    }
+
+    #[test]
+    fn negative_address() {
+        let program = [4, -1, 99];
+
+        let mut icc = IntCodeComputer::new(&program);
+        assert_eq!(
+            Err(IntcodeError::NegativeAddress {
+                i_pointer: 0,
+                instruction: 4,
+                address: -1
+            }),
+            icc.compute()
+        );
+
+        let program = [109, -10, 21101, 1, 1, 5, 99];
+
+        let mut icc = IntCodeComputer::new(&program);
+        assert_eq!(
+            Err(IntcodeError::NegativeAddress {
+                i_pointer: 2,
+                instruction: 21101,
+                address: -5
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let mut icc = IntCodeComputer::new(&[1101, 1, 1, 5, 42, 0, 99]);
+        assert_eq!(
+            Err(IntcodeError::UnknownOpcode {
+                i_pointer: 4,
+                instruction: 42
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn invalid_mode() {
+        let mut icc = IntCodeComputer::new(&[1301, 1, 1, 5, 99]);
+        assert_eq!(
+            Err(IntcodeError::InvalidMode {
+                i_pointer: 0,
+                instruction: 1301,
+                param: 0,
+                mode: 3
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn write_in_immediate_mode() {
+        let mut icc = IntCodeComputer::new(&[11101, 1, 1, 5, 99]);
+        assert_eq!(
+            Err(IntcodeError::WriteInImmediateMode {
+                i_pointer: 0,
+                instruction: 11101,
+                param: 2
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn disconnected_channels() {
+        let (mut icc, input, _output) = IntCodeComputer::with_channels(&[3, 0, 99]);
+        drop(input);
+        assert_eq!(
+            Err(IntcodeError::InputDisconnected {
+                i_pointer: 0,
+                instruction: 3
+            }),
+            icc.compute()
+        );
+
+        let (mut icc, _input, output) = IntCodeComputer::with_channels(&[104, 1, 99]);
+        drop(output);
+        assert_eq!(
+            Err(IntcodeError::OutputDisconnected {
+                i_pointer: 0,
+                instruction: 104
+            }),
+            icc.compute()
+        );
+
+        // the output follows another instruction
+        let (mut icc, _input, output) =
+            IntCodeComputer::with_channels(&[1101, 1, 1, 9, 104, 1, 99, 0, 0, 0]);
+        drop(output);
+        assert_eq!(
+            Err(IntcodeError::OutputDisconnected {
+                i_pointer: 4,
+                instruction: 104
+            }),
+            icc.compute()
+        );
+    }
+
+    #[test]
+    fn queue_returns_control() {
+        // queues cannot wait for input, so control is returned even without `return_control`
+        let mut icc = IntCodeComputer::new(&[3, 0, 4, 0, 99]);
+        assert_eq!(Ok(RunState::AwaitingInput), icc.compute());
+        icc.input.push_back(7);
+        assert_eq!(Ok(RunState::Halted), icc.compute());
+        assert_eq!(Some(7), icc.output.pop_front());
+    }
+
+    #[test]
+    fn custom_devices() {
+        let input = Recorder::new(FnInput(|| Some(3)));
+        let mut output = Vec::new();
+        let mut icc = IntCodeComputer::with_io(&[3, 0, 4, 0, 104, 5, 99], input, &mut output);
+        assert_eq!(Ok(RunState::Halted), icc.compute());
+        assert_eq!(vec![3], icc.input.log);
+        assert_eq!(vec![3, 5], output);
+    }
+
+    #[test]
+    fn step_limit() {
+        // endless loop: jump to zero
+        let mut icc = IntCodeComputer::new(&[1105, 1, 0]);
+        icc.set_max_steps(Some(1000));
+        assert_eq!(
+            Err(IntcodeError::StepLimitExceeded {
+                i_pointer: 0,
+                instruction: 1105,
+                steps: 1000
+            }),
+            icc.compute()
+        );
+        assert_eq!(1000, icc.steps());
+    }
+
+    #[test]
+    fn run_states() {
+        let mut icc = IntCodeComputer::new(&[3, 0, 99]);
+        icc.set_return_control(true);
+        assert_eq!(Ok(RunState::AwaitingInput), icc.compute());
+        icc.input.push_back(1);
+        assert_eq!(Ok(RunState::Halted), icc.compute());
+        assert_eq!(2, icc.steps());
+    }
+
+    #[test]
+    fn single_steps() {
+        let mut icc = IntCodeComputer::new(&[1101, 2, 3, 7, 4, 7, 99, 0]);
+        assert_eq!(Ok(None), icc.step());
+        assert_eq!(Ok(Some(RunState::Output(5))), icc.step());
+        assert!(!icc.finished());
+        assert_eq!(Ok(Some(RunState::Halted)), icc.step());
+        assert!(icc.finished());
+
+        // a halted computer stays halted
+        assert_eq!(Ok(Some(RunState::Halted)), icc.step());
+        assert_eq!(3, icc.steps());
+        assert!(icc.output.is_empty());
+    }
+
+    #[test]
+    fn run_as_coroutine() {
+        // test program taken from https://adventofcode.com/2019/day/9
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        let mut icc = IntCodeComputer::new(&program);
+        let mut result = Vec::new();
+        while let RunState::Output(value) = icc.run().unwrap() {
+            result.push(value);
+        }
+        assert_eq!(program.to_vec(), result);
+        assert!(icc.finished());
+    }
+
+    #[test]
+    fn fork_at_input() {
+        // output every input plus the number of inputs read so far
+        let mut program = vec![3, 20, 1001, 21, 1, 21, 1, 20, 21, 22, 4, 22, 1105, 1, 0];
+        program.resize(23, 0);
+
+        let (mut icc, tx, rx) = IntCodeComputer::with_channels(&program);
+        icc.set_return_control(true);
+        tx.send(10).unwrap();
+        assert_eq!(Ok(RunState::AwaitingInput), icc.compute());
+        assert_eq!(Ok(11), rx.recv());
+
+        let mut first = icc.fork();
+        let mut second = icc.fork();
+        first.input.push_back(1);
+        second.input.push_back(5);
+        assert_eq!(Ok(RunState::Output(3)), first.run());
+        assert_eq!(Ok(RunState::Output(7)), second.run());
+
+        // forks change neither each other nor the original
+        first.patch_memory(21, 100);
+        assert_eq!(2, second.memory().read(21));
+        assert_eq!(1, icc.memory().read(21));
+        tx.send(7).unwrap();
+        assert_eq!(Ok(RunState::Output(9)), icc.run());
+    }
+
+    #[test]
+    fn time_budget() {
+        // jump to itself forever
+        let mut icc = IntCodeComputer::new(&[1105, 1, 0]);
+        icc.set_time_budget(Some(Duration::from_millis(10)));
+        let err = icc.compute().unwrap_err();
+        assert!(matches!(
+            err,
+            IntcodeError::TimeBudgetExceeded { i_pointer: 0, .. }
+        ));
+        assert!(icc.steps() >= 1024);
+    }
+
+    #[test]
+    fn infinite_loops() {
+        let mut icc = IntCodeComputer::new(&[1105, 1, 0]);
+        icc.set_loop_detection(true);
+        assert_eq!(
+            Err(IntcodeError::InfiniteLoop {
+                i_pointer: 0,
+                instruction: 1105,
+                period: 1
+            }),
+            icc.compute()
+        );
+
+        // toggle a cell between 0 and 1, then jump back
+        let mut icc = IntCodeComputer::new(&[1008, 7, 0, 7, 1105, 1, 0, 0]);
+        icc.set_loop_detection(true);
+        let err = icc.compute().unwrap_err();
+        assert!(matches!(err, IntcodeError::InfiniteLoop { period: 4, .. }));
+        assert!(icc.steps() < 100);
+
+        // halting is not a loop
+        let mut icc = IntCodeComputer::new(&[1106, 0, 3, 99]);
+        icc.set_loop_detection(true);
+        assert_eq!(Ok(RunState::Halted), icc.compute());
+
+        // counting up never repeats a state, output is not a loop
+        let mut icc = IntCodeComputer::new(&[1001, 7, 1, 7, 1105, 1, 0, 0]);
+        icc.set_loop_detection(true);
+        icc.set_max_steps(Some(10_000));
+        assert!(matches!(
+            icc.compute(),
+            Err(IntcodeError::StepLimitExceeded { .. })
+        ));
+        let mut icc = IntCodeComputer::new(&[104, 1, 1105, 1, 0]);
+        icc.set_loop_detection(true);
+        icc.set_max_steps(Some(10_000));
+        assert!(matches!(
+            icc.compute(),
+            Err(IntcodeError::StepLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn decode_cache() {
+        // the first instruction adds, until the program turns it into a multiplication
+        let program = [
+            1101, 2, 3, 30, 4, 30, 1001, 31, 1, 31, 1101, 1, 1101, 0, 1008, 31, 2, 32, 1006, 32, 0,
+            99,
+        ];
+        for &cache in &[false, true] {
+            let mut icc = IntCodeComputer::new(&program);
+            icc.set_decode_cache(cache);
+            icc.compute().unwrap();
+            assert_eq!(vec![5, 6], Vec::from(icc.output));
+        }
+
+        for &input in &[1, 5] {
+            let outputs: Vec<_> = [false, true]
+                .iter()
+                .map(|&cache| {
+                    let mut icc = IntCodeComputer::new(&INTCODE_TEST_PROGRAM);
+                    icc.set_decode_cache(cache);
+                    icc.input.push_back(input);
+                    icc.compute().unwrap();
+                    icc.output
+                })
+                .collect();
+            assert_eq!(outputs[0], outputs[1]);
+        }
+
+        // a far jump allocates a single page of the cache
+        let mut icc = IntCodeComputer::new(&[1105_i64, 1, 1 << 40]);
+        icc.set_decode_cache(true);
+        assert_eq!(
+            Err(IntcodeError::UnknownOpcode {
+                i_pointer: 1 << 40,
+                instruction: 0
+            }),
+            icc.compute()
+        );
+    }
+}
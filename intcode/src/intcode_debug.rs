@@ -0,0 +1,333 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+
+use crate::intcode_cmp::{IntCodeComputer, RunState};
+use crate::intcode_disasm::Instruction;
+use crate::intcode_error::IntcodeError;
+use crate::intcode_io::{InputDevice, OutputDevice};
+use crate::intcode_word::Word;
+
+/// Condition on output values, which stops the debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCondition<W> {
+    Any,
+    Equal(W),
+    NotEqual(W),
+    Less(W),
+    Greater(W),
+}
+
+impl<W: Word> OutputCondition<W> {
+    /// Whether the output value fulfills the condition.
+    pub fn matches(&self, value: W) -> bool {
+        match *self {
+            OutputCondition::Any => true,
+            OutputCondition::Equal(other) => value == other,
+            OutputCondition::NotEqual(other) => value != other,
+            OutputCondition::Less(other) => value < other,
+            OutputCondition::Greater(other) => value > other,
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for OutputCondition<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputCondition::Any => write!(f, "any"),
+            OutputCondition::Equal(value) => write!(f, "={}", value),
+            OutputCondition::NotEqual(value) => write!(f, "!={}", value),
+            OutputCondition::Less(value) => write!(f, "<{}", value),
+            OutputCondition::Greater(value) => write!(f, ">{}", value),
+        }
+    }
+}
+
+/// Reason why the debugger stopped the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop<W> {
+    /// The requested number of steps is executed.
+    Stepped,
+    /// The next instruction is at a breakpoint.
+    Breakpoint(usize),
+    /// A watched memory cell changed.
+    Watchpoint { address: usize, old: W, new: W },
+    /// An output value matched an output condition.
+    Output(W),
+    /// The program needs input, but none is available.
+    AwaitingInput,
+    /// The program has halted.
+    Halted,
+}
+
+/// Debugger for the Intcode computer.
+///
+/// Runs a program step by step or until a breakpoint (on the address of the next instruction),
+/// a watchpoint (on a change of a memory cell) or an output condition is hit. Output values are
+/// written to the output device of the computer, just like with `IntCodeComputer::compute`.
+///
+/// ```
+/// use intcode::intcode_cmp::IntCodeComputer;
+/// use intcode::intcode_debug::{Debugger, Stop};
+///
+/// let program = [1101, 2, 3, 7, 4, 7, 99, 0];
+///
+/// let mut debugger = Debugger::new(IntCodeComputer::new(&program));
+/// debugger.toggle_breakpoint(4);
+/// assert_eq!(Stop::Breakpoint(4), debugger.cont().unwrap());
+/// assert_eq!("out [7]", debugger.current_instruction().unwrap().to_string());
+/// assert_eq!(5, debugger.icc.memory().read(7));
+/// assert_eq!(Stop::Halted, debugger.cont().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Debugger<W: Word, I, O> {
+    pub icc: IntCodeComputer<W, I, O>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    output_conditions: Vec<OutputCondition<W>>,
+    /// Stops of the last instruction, which are not reported yet
+    pending: VecDeque<Stop<W>>,
+}
+
+impl<W: Word, I: InputDevice<W>, O: OutputDevice<W>> Debugger<W, I, O> {
+    /// * `icc` - computer to debug; `return_control` is switched on, so the debugger can ask for
+    ///   input instead of blocking
+    pub fn new(mut icc: IntCodeComputer<W, I, O>) -> Debugger<W, I, O> {
+        icc.set_return_control(true);
+        Debugger {
+            icc,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            output_conditions: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Set or remove a breakpoint. Returns whether the breakpoint is set now.
+    pub fn toggle_breakpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.breakpoints, address)
+    }
+
+    /// Set or remove a watchpoint. Returns whether the watchpoint is set now.
+    pub fn toggle_watchpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.watchpoints, address)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &BTreeSet<usize> {
+        &self.watchpoints
+    }
+
+    /// Stop, when an output value matches the condition.
+    pub fn add_output_condition(&mut self, condition: OutputCondition<W>) {
+        self.output_conditions.push(condition);
+    }
+
+    pub fn clear_output_conditions(&mut self) {
+        self.output_conditions.clear();
+    }
+
+    pub fn output_conditions(&self) -> &[OutputCondition<W>] {
+        &self.output_conditions
+    }
+
+    /// Decode the next instruction, if it is valid.
+    pub fn current_instruction(&self) -> Option<Instruction<W>> {
+        let address = self.icc.i_pointer();
+        let words: Vec<W> = (address..address.saturating_add(4))
+            .map(|address| self.icc.memory().read(address))
+            .collect();
+        Instruction::decode(&words)
+    }
+
+    /// Execute a single instruction, unless the program waits for input or has halted.
+    ///
+    /// If the instruction causes several stops, e.g. it outputs a value and changes a watched
+    /// cell, the first one is returned, and the others on the next calls, without executing
+    /// further instructions.
+    fn step_once(&mut self) -> Result<Option<Stop<W>>, IntcodeError> {
+        if let Some(stop) = self.pending.pop_front() {
+            return Ok(Some(stop));
+        }
+        let watched: Vec<(usize, W)> = self
+            .watchpoints
+            .iter()
+            .map(|&address| (address, self.icc.memory().read(address)))
+            .collect();
+
+        let i_pointer = self.icc.i_pointer();
+        let output = match self.icc.step()? {
+            None => None,
+            Some(RunState::Output(value)) => {
+                if self.icc.output.write(value).is_err() {
+                    return Err(IntcodeError::OutputDisconnected {
+                        i_pointer,
+                        instruction: self.icc.memory().read(i_pointer).to_i128(),
+                    });
+                }
+                Some(value)
+            }
+            Some(RunState::AwaitingInput) => return Ok(Some(Stop::AwaitingInput)),
+            Some(RunState::Halted) => return Ok(Some(Stop::Halted)),
+        };
+
+        for (address, old) in watched {
+            let new = self.icc.memory().read(address);
+            if new != old {
+                self.pending
+                    .push_back(Stop::Watchpoint { address, old, new });
+            }
+        }
+        if let Some(value) = output {
+            if self.output_conditions.iter().any(|c| c.matches(value)) {
+                self.pending.push_back(Stop::Output(value));
+            }
+        }
+        if self.breakpoints.contains(&self.icc.i_pointer()) {
+            self.pending
+                .push_back(Stop::Breakpoint(self.icc.i_pointer()));
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    /// Execute up to `count` instructions, stopping early at breakpoints and the like.
+    pub fn step(&mut self, count: usize) -> Result<Stop<W>, IntcodeError> {
+        for _ in 0..count {
+            if let Some(stop) = self.step_once()? {
+                return Ok(stop);
+            }
+        }
+        Ok(Stop::Stepped)
+    }
+
+    /// Continue the program until it stops at a breakpoint, watchpoint or output condition, waits
+    /// for input or halts.
+    pub fn cont(&mut self) -> Result<Stop<W>, IntcodeError> {
+        loop {
+            if let Some(stop) = self.step_once()? {
+                return Ok(stop);
+            }
+        }
+    }
+}
+
+fn toggle(set: &mut BTreeSet<usize>, address: usize) -> bool {
+    if set.remove(&address) {
+        false
+    } else {
+        set.insert(address);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_cmp::IntCodeComputer;
+    use crate::intcode_debug::{Debugger, OutputCondition, Stop};
+    use crate::intcode_opcodes::{CustomOp, Effect, OpcodeRegistry, Param};
+
+    // count down from the input value to one
+    const COUNTDOWN: [i64; 13] = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+
+    #[test]
+    fn single_steps() {
+        let mut debugger = Debugger::new(IntCodeComputer::new(&COUNTDOWN));
+        assert_eq!(Ok(Stop::AwaitingInput), debugger.step(1));
+        debugger.icc.input.push_back(2);
+        assert_eq!(Ok(Stop::Stepped), debugger.step(2));
+        assert_eq!(4, debugger.icc.i_pointer());
+        assert_eq!(Some(2), debugger.icc.output.pop_front());
+        assert_eq!(
+            "add [12], #-1, [12]",
+            debugger.current_instruction().unwrap().to_string()
+        );
+        assert_eq!(Ok(Stop::Halted), debugger.step(100));
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        let mut debugger = Debugger::new(IntCodeComputer::new(&COUNTDOWN));
+        debugger.icc.input.push_back(3);
+        assert!(debugger.toggle_breakpoint(8));
+        assert_eq!(Ok(Stop::Breakpoint(8)), debugger.cont());
+        assert_eq!(Ok(Stop::Breakpoint(8)), debugger.cont());
+        assert!(!debugger.toggle_breakpoint(8));
+
+        debugger.toggle_watchpoint(12);
+        assert_eq!(
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 1,
+                new: 0
+            }),
+            debugger.cont()
+        );
+        assert_eq!(Ok(Stop::Halted), debugger.cont());
+        assert_eq!(
+            vec![3, 2, 1],
+            debugger.icc.output.iter().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn output_conditions() {
+        let mut debugger = Debugger::new(IntCodeComputer::new(&COUNTDOWN));
+        debugger.icc.input.push_back(5);
+        debugger.add_output_condition(OutputCondition::Less(4));
+        assert_eq!(Ok(Stop::Output(3)), debugger.cont());
+        assert_eq!(Ok(Stop::Output(2)), debugger.cont());
+
+        debugger.clear_output_conditions();
+        debugger.add_output_condition(OutputCondition::Equal(5));
+        assert_eq!(Ok(Stop::Halted), debugger.cont());
+    }
+
+    #[test]
+    fn several_stops() {
+        // 50 writes its operand plus one and outputs its operand
+        let tee = CustomOp::new("tee", &[Param::Read, Param::Write], |args: &[i64]| {
+            Ok(Effect {
+                output: Some(args[0]),
+                ..Effect::writes(vec![args[0] + 1])
+            })
+        });
+        let mut registry = OpcodeRegistry::new();
+        registry.register(50, tee).unwrap();
+        let mut icc = IntCodeComputer::new(&[150, 7, 5, 99, 99, 0]);
+        icc.set_opcodes(Some(registry));
+
+        let mut debugger = Debugger::new(icc);
+        debugger.toggle_watchpoint(5);
+        debugger.toggle_breakpoint(3);
+        debugger.add_output_condition(OutputCondition::Equal(7));
+        assert_eq!(
+            Ok(Stop::Watchpoint {
+                address: 5,
+                old: 0,
+                new: 8
+            }),
+            debugger.cont()
+        );
+        assert_eq!(Ok(Stop::Output(7)), debugger.cont());
+        assert_eq!(Ok(Stop::Breakpoint(3)), debugger.cont());
+        assert_eq!(3, debugger.icc.i_pointer());
+        assert_eq!(Ok(Stop::Halted), debugger.cont());
+    }
+
+    #[test]
+    fn patch_memory() {
+        let mut debugger = Debugger::new(IntCodeComputer::new(&COUNTDOWN));
+        debugger.icc.input.push_back(3);
+        debugger.step(1).unwrap();
+
+        // count down in steps of three
+        debugger.icc.patch_memory(6, -3);
+        assert_eq!(Ok(Stop::Halted), debugger.cont());
+        assert_eq!(
+            vec![3],
+            debugger.icc.output.iter().copied().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use crate::intcode_mem::{Memory, NEAR_PAGES, PAGE_BITS, PAGE_SIZE};
+use crate::intcode_word::Word;
+
+/// Instruction split into its opcode and the modes of its (up to three) parameters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Decoded {
+    pub opcode: u8,
+    pub modes: [u8; 3],
+}
+
+impl Decoded {
+    /// Decode an instruction. Negative instructions have no valid opcode and give `None`.
+    pub fn new(instruction: i128) -> Option<Decoded> {
+        if instruction < 0 {
+            return None;
+        }
+        let digit = |div: i128| (instruction / div % 10) as u8;
+        Some(Decoded {
+            opcode: (instruction % 100) as u8,
+            modes: [digit(100), digit(1000), digit(10000)],
+        })
+    }
+}
+
+type Page = Box<[Option<Decoded>; PAGE_SIZE]>;
+
+/// Cache of decoded instructions by their address.
+///
+/// An entry only depends on the memory cell at its address, as parameters are read when the
+/// instruction is executed. So a write to a cell invalidates the entry of that cell, and
+/// self-modifying programs run just like without the cache.
+///
+/// Entries are organised in pages like `Memory`: contiguous pages from address zero upwards, and
+/// a sparse map of pages far beyond, so a jump to a far address allocates a single page only.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecodeCache {
+    pages: Vec<Page>,
+    far_pages: HashMap<usize, Page>,
+}
+
+impl DecodeCache {
+    fn empty_page() -> Page {
+        Box::new([None; PAGE_SIZE])
+    }
+
+    fn page(&mut self, page: usize) -> Option<&mut Page> {
+        match self.pages.get_mut(page) {
+            Some(entries) => Some(entries),
+            None => self.far_pages.get_mut(&page),
+        }
+    }
+
+    fn page_or_insert(&mut self, page: usize) -> &mut Page {
+        // far pages lie beyond the contiguous part, as they are pulled into it when it grows
+        if page < self.pages.len() {
+            &mut self.pages[page]
+        } else if page < self.pages.len() + NEAR_PAGES && !self.far_pages.contains_key(&page) {
+            while self.pages.len() <= page {
+                let next = self
+                    .far_pages
+                    .remove(&self.pages.len())
+                    .unwrap_or_else(Self::empty_page);
+                self.pages.push(next);
+            }
+            &mut self.pages[page]
+        } else {
+            self.far_pages.entry(page).or_insert_with(Self::empty_page)
+        }
+    }
+
+    /// Decode the instruction at the address, or look it up if it was decoded before.
+    pub fn get<W: Word>(&mut self, address: usize, memory: &Memory<W>) -> Option<Decoded> {
+        let (page, offset) = (address >> PAGE_BITS, address % PAGE_SIZE);
+        if let Some(Some(decoded)) = self.page(page).map(|entries| entries[offset]) {
+            return Some(decoded);
+        }
+        let decoded = Decoded::new(memory.read(address).to_i128())?;
+        self.page_or_insert(page)[offset] = Some(decoded);
+        Some(decoded)
+    }
+
+    /// Forget the decoded instruction at the address, e.g. after a write to it.
+    pub fn invalidate(&mut self, address: usize) {
+        if let Some(entries) = self.page(address >> PAGE_BITS) {
+            entries[address % PAGE_SIZE] = None;
+        }
+    }
+
+    /// Number of entries currently allocated.
+    #[cfg(test)]
+    fn allocated(&self) -> usize {
+        (self.pages.len() + self.far_pages.len()) * PAGE_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_decode::{DecodeCache, Decoded};
+    use crate::intcode_mem::{Memory, PAGE_SIZE};
+
+    #[test]
+    fn decode() {
+        let decoded = Decoded::new(21002).unwrap();
+        assert_eq!(2, decoded.opcode);
+        assert_eq!([0, 1, 2], decoded.modes);
+        assert_eq!(Some(Decoded::default()), Decoded::new(0));
+        assert_eq!(None, Decoded::new(-1));
+    }
+
+    #[test]
+    fn invalidation() {
+        let mut memory = Memory::new(&[1101_i64, 1, 2, 0, 99]);
+        let mut cache = DecodeCache::default();
+        assert_eq!(1, cache.get(0, &memory).unwrap().opcode);
+
+        // the cache does not watch the memory by itself
+        memory.write(0, 1102);
+        assert_eq!(1, cache.get(0, &memory).unwrap().opcode);
+        cache.invalidate(0);
+        assert_eq!(2, cache.get(0, &memory).unwrap().opcode);
+
+        cache.invalidate(1000);
+        assert_eq!(99, cache.get(4, &memory).unwrap().opcode);
+    }
+
+    #[test]
+    fn far_addresses() {
+        let mut memory = Memory::new(&[99_i64]);
+        memory.write(1 << 40, 1101);
+        let mut cache = DecodeCache::default();
+        assert_eq!(1, cache.get(1 << 40, &memory).unwrap().opcode);
+        assert_eq!(0, cache.get(1_000_000_000, &memory).unwrap().opcode);
+        assert_eq!(99, cache.get(0, &memory).unwrap().opcode);
+        assert_eq!(3 * PAGE_SIZE, cache.allocated());
+
+        cache.invalidate(1 << 41);
+        assert_eq!(3 * PAGE_SIZE, cache.allocated());
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::intcode_word::Word;
+
+/// Static description of an Intcode instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpInfo {
+    pub opcode: i128,
+    pub mnemonic: &'static str,
+    /// Number of parameters
+    pub params: usize,
+    /// Whether the last parameter is the address written to
+    pub writes: bool,
+}
+
+/// All instructions known to the Intcode computer.
+pub const OPS: [OpInfo; 10] = [
+    OpInfo {
+        opcode: 1,
+        mnemonic: "add",
+        params: 3,
+        writes: true,
+    },
+    OpInfo {
+        opcode: 2,
+        mnemonic: "mul",
+        params: 3,
+        writes: true,
+    },
+    OpInfo {
+        opcode: 3,
+        mnemonic: "in",
+        params: 1,
+        writes: true,
+    },
+    OpInfo {
+        opcode: 4,
+        mnemonic: "out",
+        params: 1,
+        writes: false,
+    },
+    OpInfo {
+        opcode: 5,
+        mnemonic: "jnz",
+        params: 2,
+        writes: false,
+    },
+    OpInfo {
+        opcode: 6,
+        mnemonic: "jz",
+        params: 2,
+        writes: false,
+    },
+    OpInfo {
+        opcode: 7,
+        mnemonic: "lt",
+        params: 3,
+        writes: true,
+    },
+    OpInfo {
+        opcode: 8,
+        mnemonic: "eq",
+        params: 3,
+        writes: true,
+    },
+    OpInfo {
+        opcode: 9,
+        mnemonic: "arb",
+        params: 1,
+        writes: false,
+    },
+    OpInfo {
+        opcode: 99,
+        mnemonic: "hlt",
+        params: 0,
+        writes: false,
+    },
+];
+
+/// Look up an instruction by its opcode.
+pub fn op_info(opcode: i128) -> Option<&'static OpInfo> {
+    OPS.iter().find(|op| op.opcode == opcode)
+}
+
+/// Look up an instruction by its mnemonic.
+pub fn op_by_mnemonic(mnemonic: &str) -> Option<&'static OpInfo> {
+    OPS.iter().find(|op| op.mnemonic == mnemonic)
+}
+
+/// Parameter mode of an operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn from_digit(digit: i128) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    /// Digit of the mode in an instruction.
+    pub fn digit(self) -> i128 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// Operand of a decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand<W> {
+    pub mode: Mode,
+    pub value: W,
+}
+
+/// Operands are written as `[225]` (position), `#5` (immediate) or `rb+3` (relative).
+impl<W: Word> fmt::Display for Operand<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value.to_i128() < 0 => write!(f, "rb{}", self.value),
+            Mode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+/// Way an instruction continues, see `Instruction::flow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Execution continues with the next instruction at the address.
+    Next(usize),
+    /// A jump with a target in immediate mode is taken.
+    Jump(usize),
+    /// A jump to a target, which is only known at runtime, is taken.
+    ComputedJump,
+}
+
+/// Decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction<W> {
+    pub op: &'static OpInfo,
+    pub operands: Vec<Operand<W>>,
+}
+
+impl<W: Word> Instruction<W> {
+    /// Decode the instruction at the start of `words`.
+    ///
+    /// Returns `None` if the words are no valid instruction: unknown opcodes, unknown modes,
+    /// writes in immediate mode, mode digits for parameters which do not exist, or missing
+    /// parameters.
+    pub fn decode(words: &[W]) -> Option<Instruction<W>> {
+        let instruction = words.first()?.to_i128();
+        if instruction < 0 {
+            return None;
+        }
+        let op = op_info(instruction % 100)?;
+        let mut modes = instruction / 100;
+        let mut operands = Vec::with_capacity(op.params);
+        for number in 0..op.params {
+            let mode = Mode::from_digit(modes % 10)?;
+            if op.writes && number + 1 == op.params && mode == Mode::Immediate {
+                return None;
+            }
+            let value = *words.get(number + 1)?;
+            operands.push(Operand { mode, value });
+            modes /= 10;
+        }
+        if modes != 0 {
+            return None;
+        }
+        Some(Instruction { op, operands })
+    }
+
+    /// Number of words of the instruction.
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    /// Encode the instruction into words.
+    pub fn encode(&self) -> Vec<W> {
+        let mut instruction = self.op.opcode;
+        let mut factor = 100;
+        for operand in &self.operands {
+            instruction += operand.mode.digit() * factor;
+            factor *= 10;
+        }
+        let mut words = vec![W::from(instruction as i32)];
+        words.extend(self.operands.iter().map(|operand| operand.value));
+        words
+    }
+
+    /// Ways the instruction may continue, as far as they are known statically.
+    ///
+    /// * `address` - address of the instruction
+    pub fn flow(&self, address: usize) -> Vec<Flow> {
+        let next = Flow::Next(address + self.size());
+        match self.op.opcode {
+            5 | 6 => {
+                let condition = self.operands[0];
+                let target = self.operands[1];
+                let mut flow = Vec::new();
+
+                // a condition in immediate mode decides the branch statically
+                let jumps = if condition.mode == Mode::Immediate {
+                    Some((condition.value.to_i128() != 0) == (self.op.opcode == 5))
+                } else {
+                    None
+                };
+                if jumps != Some(false) {
+                    if target.mode != Mode::Immediate {
+                        flow.push(Flow::ComputedJump);
+                    } else if let Ok(target) = usize::try_from(target.value.to_i128()) {
+                        flow.push(Flow::Jump(target));
+                    }
+                }
+                if jumps != Some(true) {
+                    flow.push(next);
+                }
+                flow
+            }
+            99 => Vec::new(),
+            _ => vec![next],
+        }
+    }
+
+    /// Addresses the instruction may continue with, as far as they are known statically.
+    ///
+    /// * `address` - address of the instruction
+    pub fn successors(&self, address: usize) -> Vec<usize> {
+        self.flow(address)
+            .into_iter()
+            .filter_map(|flow| match flow {
+                Flow::Next(address) | Flow::Jump(address) => Some(address),
+                Flow::ComputedJump => None,
+            })
+            .collect()
+    }
+}
+
+impl<W: Word> fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic)?;
+        for (number, operand) in self.operands.iter().enumerate() {
+            let separator = if number == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// Content of a line of the listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item<W> {
+    Instruction(Instruction<W>),
+    Data(W),
+}
+
+/// Line of the listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line<W> {
+    pub address: usize,
+    pub item: Item<W>,
+}
+
+impl<W: Word> fmt::Display for Line<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.item {
+            Item::Instruction(instruction) => write!(f, "{:>5}: {}", self.address, instruction),
+            Item::Data(value) => write!(f, "{:>5}: data {}", self.address, value),
+        }
+    }
+}
+
+/// Disassembled program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing<W> {
+    pub lines: Vec<Line<W>>,
+}
+
+impl<W: Word> fmt::Display for Listing<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the addresses of all instructions reachable from the entry points.
+///
+/// Control flow is followed through jumps with targets in immediate mode. Targets of computed
+/// jumps are not known statically, so the code behind them is only found if given as entry point.
+pub fn reachable<W: Word>(program: &[W], entries: &[usize]) -> BTreeSet<usize> {
+    let mut found = BTreeSet::new();
+    let mut todo = entries.to_vec();
+    while let Some(address) = todo.pop() {
+        if address >= program.len() || found.contains(&address) {
+            continue;
+        }
+        if let Some(instruction) = Instruction::decode(&program[address..]) {
+            found.insert(address);
+            todo.extend(instruction.successors(address));
+        }
+    }
+    found
+}
+
+/// Disassemble a program, which starts at address zero.
+///
+/// * `program` - program instructions and data
+pub fn disassemble<W: Word>(program: &[W]) -> Listing<W> {
+    disassemble_from(program, &[0])
+}
+
+/// Disassemble a program with additional entry points, e.g. targets of computed jumps.
+///
+/// Words, which are not part of a reachable instruction, are listed as `data`.
+///
+/// * `program` - program instructions and data
+/// * `entries` - addresses to start following the control flow from
+pub fn disassemble_from<W: Word>(program: &[W], entries: &[usize]) -> Listing<W> {
+    let code = reachable(program, entries);
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let instruction = if code.contains(&address) {
+            Instruction::decode(&program[address..])
+        } else {
+            None
+        };
+        let (item, len) = match instruction {
+            Some(instruction) => {
+                let len = instruction.size();
+                (Item::Instruction(instruction), len)
+            }
+            None => (Item::Data(program[address]), 1),
+        };
+        lines.push(Line { address, item });
+        address += len;
+    }
+    Listing { lines }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_disasm::{disassemble, disassemble_from, Flow, Instruction, Item};
+
+    #[test]
+    fn decode_operands() {
+        let instruction = Instruction::decode(&[21002, 225, 5, -3]).unwrap();
+        assert_eq!("mul [225], #5, rb-3", instruction.to_string());
+        assert_eq!(vec![21002, 225, 5, -3], instruction.encode());
+
+        let instruction = Instruction::decode(&[204, 3]).unwrap();
+        assert_eq!("out rb+3", instruction.to_string());
+    }
+
+    #[test]
+    fn decode_invalid() {
+        // unknown opcode, unknown mode, write in immediate mode, superfluous mode, missing param
+        assert_eq!(None, Instruction::decode(&[42, 0, 0, 0]));
+        assert_eq!(None, Instruction::decode(&[301, 0, 0, 0]));
+        assert_eq!(None, Instruction::decode(&[11101, 0, 0, 0]));
+        assert_eq!(None, Instruction::decode(&[199]));
+        assert_eq!(None, Instruction::decode(&[1, 0, 0]));
+    }
+
+    #[test]
+    fn listing() {
+        // test program taken from https://adventofcode.com/2019/day/5
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let expected = "    0: in [12]
+    2: jz [12], [15]
+    5: add [13], [14], [13]
+    9: out [13]
+   11: hlt
+   12: data -1
+   13: data 0
+   14: data 1
+   15: data 9
+";
+        assert_eq!(expected, disassemble(&program).to_string());
+    }
+
+    #[test]
+    fn static_jumps() {
+        // jump over data, never fall through an unconditional jump
+        let program = [1105, 1, 4, 42, 104, 1, 99];
+        let listing = disassemble(&program);
+        let items: Vec<String> = listing.lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            vec![
+                "    0: jnz #1, #4",
+                "    3: data 42",
+                "    4: out #1",
+                "    6: hlt"
+            ],
+            items
+        );
+
+        // code behind a computed jump is data, unless given as entry point
+        let program = [1, 0, 0, 0, 99];
+        let listing = disassemble_from(&program, &[4]);
+        assert_eq!(Item::Data(1), listing.lines[0].item);
+        assert_eq!("    4: hlt", listing.lines[4].to_string());
+    }
+
+    #[test]
+    fn flow() {
+        let flow = |words: &[i64]| Instruction::decode(words).unwrap().flow(10);
+        assert_eq!(vec![Flow::Jump(4)], flow(&[1105, 1, 4]));
+        assert_eq!(vec![Flow::Next(13)], flow(&[1106, 1, 4]));
+        assert_eq!(vec![Flow::Jump(4), Flow::Next(13)], flow(&[1005, 0, 4]));
+        assert_eq!(vec![Flow::ComputedJump, Flow::Next(13)], flow(&[5, 0, 4]));
+        assert_eq!(Vec::<Flow>::new(), flow(&[99]));
+        // computed targets are not among the successors
+        let instruction = Instruction::decode(&[5, 0, 4]).unwrap();
+        assert_eq!(vec![13], instruction.successors(10));
+    }
+}
@@ -0,0 +1,217 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Error raised while running a program on the Intcode computer.
+///
+/// Every variant carries the instruction pointer and the raw instruction, at which the error
+/// occurred. The instruction is widened to `i128`, so errors look the same for all word types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    /// The opcode of the instruction is not known.
+    UnknownOpcode { i_pointer: usize, instruction: i128 },
+
+    /// The parameter mode of the `param`-th parameter (counting from zero) is not known.
+    InvalidMode {
+        i_pointer: usize,
+        instruction: i128,
+        param: usize,
+        mode: i128,
+    },
+
+    /// The `param`-th parameter (counting from zero) is written to, but is in immediate mode.
+    WriteInImmediateMode {
+        i_pointer: usize,
+        instruction: i128,
+        param: usize,
+    },
+
+    /// An arithmetic instruction overflowed the word type (see `Overflow::Checked`).
+    Overflow { i_pointer: usize, instruction: i128 },
+
+    /// An address computed by the program is negative.
+    NegativeAddress {
+        i_pointer: usize,
+        instruction: i128,
+        address: i128,
+    },
+
+    /// An address computed by the program does not fit into the address space of the host.
+    AddressOutOfRange {
+        i_pointer: usize,
+        instruction: i128,
+        address: i128,
+    },
+
+    /// The program reads input, but the input device is disconnected.
+    InputDisconnected { i_pointer: usize, instruction: i128 },
+
+    /// The program writes output, but the output device is disconnected.
+    OutputDisconnected { i_pointer: usize, instruction: i128 },
+
+    /// The maximum number of steps (see `IntCodeComputer::set_max_steps`) is reached.
+    StepLimitExceeded {
+        i_pointer: usize,
+        instruction: i128,
+        steps: u64,
+    },
+
+    /// The time budget (see `IntCodeComputer::set_time_budget`) is used up.
+    TimeBudgetExceeded {
+        i_pointer: usize,
+        instruction: i128,
+        budget: Duration,
+    },
+
+    /// The machine is in the same state as `period` instructions before, without any I/O in
+    /// between, so it loops forever (see `IntCodeComputer::set_loop_detection`).
+    InfiniteLoop {
+        i_pointer: usize,
+        instruction: i128,
+        period: u64,
+    },
+
+    /// The handler of a custom instruction (see `intcode_opcodes`) failed, or did not return one
+    /// value for every parameter written to.
+    CustomInstructionFailed {
+        i_pointer: usize,
+        instruction: i128,
+        message: String,
+    },
+}
+
+impl IntcodeError {
+    /// Instruction pointer at which the error occurred.
+    pub fn i_pointer(&self) -> usize {
+        match *self {
+            IntcodeError::UnknownOpcode { i_pointer, .. }
+            | IntcodeError::InvalidMode { i_pointer, .. }
+            | IntcodeError::WriteInImmediateMode { i_pointer, .. }
+            | IntcodeError::Overflow { i_pointer, .. }
+            | IntcodeError::NegativeAddress { i_pointer, .. }
+            | IntcodeError::AddressOutOfRange { i_pointer, .. }
+            | IntcodeError::InputDisconnected { i_pointer, .. }
+            | IntcodeError::OutputDisconnected { i_pointer, .. }
+            | IntcodeError::StepLimitExceeded { i_pointer, .. }
+            | IntcodeError::TimeBudgetExceeded { i_pointer, .. }
+            | IntcodeError::InfiniteLoop { i_pointer, .. }
+            | IntcodeError::CustomInstructionFailed { i_pointer, .. } => i_pointer,
+        }
+    }
+
+    /// Raw instruction at which the error occurred.
+    pub fn instruction(&self) -> i128 {
+        match *self {
+            IntcodeError::UnknownOpcode { instruction, .. }
+            | IntcodeError::InvalidMode { instruction, .. }
+            | IntcodeError::WriteInImmediateMode { instruction, .. }
+            | IntcodeError::Overflow { instruction, .. }
+            | IntcodeError::NegativeAddress { instruction, .. }
+            | IntcodeError::AddressOutOfRange { instruction, .. }
+            | IntcodeError::InputDisconnected { instruction, .. }
+            | IntcodeError::OutputDisconnected { instruction, .. }
+            | IntcodeError::StepLimitExceeded { instruction, .. }
+            | IntcodeError::TimeBudgetExceeded { instruction, .. }
+            | IntcodeError::InfiniteLoop { instruction, .. }
+            | IntcodeError::CustomInstructionFailed { instruction, .. } => instruction,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode {
+                i_pointer,
+                instruction,
+            } => write!(f, "UNKNOWN INSTRUCTION AT {}: {}", i_pointer, instruction),
+            IntcodeError::InvalidMode {
+                i_pointer,
+                instruction,
+                param,
+                mode,
+            } => write!(
+                f,
+                "INVALID MODE {} OF PARAMETER {} AT {}: {}",
+                mode, param, i_pointer, instruction
+            ),
+            IntcodeError::WriteInImmediateMode {
+                i_pointer,
+                instruction,
+                param,
+            } => write!(
+                f,
+                "WRITE TO PARAMETER {} IN IMMEDIATE MODE AT {}: {}",
+                param, i_pointer, instruction
+            ),
+            IntcodeError::Overflow {
+                i_pointer,
+                instruction,
+            } => write!(f, "OVERFLOW AT {}: {}", i_pointer, instruction),
+            IntcodeError::NegativeAddress {
+                i_pointer,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "NEGATIVE ADDRESS {} AT {}: {}",
+                address, i_pointer, instruction
+            ),
+            IntcodeError::AddressOutOfRange {
+                i_pointer,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "ADDRESS {} OUT OF RANGE AT {}: {}",
+                address, i_pointer, instruction
+            ),
+            IntcodeError::InputDisconnected {
+                i_pointer,
+                instruction,
+            } => write!(f, "INPUT DISCONNECTED AT {}: {}", i_pointer, instruction),
+            IntcodeError::OutputDisconnected {
+                i_pointer,
+                instruction,
+            } => write!(f, "OUTPUT DISCONNECTED AT {}: {}", i_pointer, instruction),
+            IntcodeError::StepLimitExceeded {
+                i_pointer,
+                instruction,
+                steps,
+            } => write!(
+                f,
+                "STEP LIMIT OF {} EXCEEDED AT {}: {}",
+                steps, i_pointer, instruction
+            ),
+            IntcodeError::TimeBudgetExceeded {
+                i_pointer,
+                instruction,
+                budget,
+            } => write!(
+                f,
+                "TIME BUDGET OF {:?} EXCEEDED AT {}: {}",
+                budget, i_pointer, instruction
+            ),
+            IntcodeError::InfiniteLoop {
+                i_pointer,
+                instruction,
+                period,
+            } => write!(
+                f,
+                "INFINITE LOOP OF {} STEPS AT {}: {}",
+                period, i_pointer, instruction
+            ),
+            IntcodeError::CustomInstructionFailed {
+                i_pointer,
+                instruction,
+                message,
+            } => write!(
+                f,
+                "CUSTOM INSTRUCTION FAILED AT {}: {}: {}",
+                i_pointer, instruction, message
+            ),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
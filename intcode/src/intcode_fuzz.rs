@@ -0,0 +1,727 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+use crate::intcode_cmp::{IntCodeComputer, RunState};
+use crate::intcode_error::IntcodeError;
+use crate::intcode_mem::Memory;
+use crate::intcode_trace::{TraceFilter, Tracer};
+use crate::intcode_word::Word;
+
+/// Pseudo-random number generator (xorshift64*), so fuzzing runs are reproducible from their seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // the state must not be zero
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Random number in `0..n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Random number in `low..high`.
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low) as u64) as i64
+    }
+}
+
+/// Options of the program generator and the fuzzing runs.
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+    /// Number of instructions of a program, apart from the loop and jump scaffolding
+    pub instructions: usize,
+    /// Number of memory cells the instructions read and write
+    pub data_cells: usize,
+    /// Maximum nesting of loops and skipped blocks
+    pub max_depth: usize,
+    /// Maximum number of iterations of a loop
+    pub max_iterations: i64,
+    /// Number of input values per run
+    pub inputs: usize,
+    /// Step limit of every run, which only shrunk programs should reach
+    pub max_steps: u64,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> FuzzOptions {
+        FuzzOptions {
+            instructions: 40,
+            data_cells: 8,
+            max_depth: 3,
+            max_iterations: 4,
+            inputs: 4,
+            max_steps: 100_000,
+        }
+    }
+}
+
+/// Operand of a generated instruction.
+enum Operand {
+    /// Data cell read or written in position mode
+    Position(usize),
+    /// Value in immediate mode
+    Immediate(i64),
+    /// Data cell read or written in relative mode
+    Relative(usize),
+}
+
+/// Generator of well-formed programs.
+///
+/// The program sets the relative base to the data cells behind the code, and all operands
+/// address these data cells, in position or relative mode, so no instruction reads or writes
+/// outside of them, and the code is never modified. Jumps go forward only, over a block of
+/// instructions, or back to the start of a loop, whose counter is kept in a cell of its own and
+/// counted down to zero. So every program terminates. Instructions `arb` within a block are
+/// undone at its end, so the relative base is the same, whether a block is skipped or not.
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    options: &'a FuzzOptions,
+    code: Vec<i64>,
+    /// Positions of data cell numbers in the code, which become addresses once the code is done
+    data_refs: Vec<usize>,
+    /// Number of loop counters, which are stored behind the data cells
+    counters: usize,
+    /// Relative base, as number of the data cell it points to
+    relative_base: i64,
+    /// Number of instructions yet to generate
+    budget: usize,
+}
+
+impl Generator<'_> {
+    fn emit(&mut self, opcode: i64, operands: &[Operand]) {
+        let mut instruction = opcode;
+        let mut params = Vec::new();
+        for (number, operand) in operands.iter().enumerate() {
+            let (mode, value) = match *operand {
+                Operand::Position(cell) => {
+                    self.data_refs.push(self.code.len() + 1 + number);
+                    (0, cell as i64)
+                }
+                Operand::Immediate(value) => (1, value),
+                Operand::Relative(cell) => (2, cell as i64 - self.relative_base),
+            };
+            instruction += mode * 10_i64.pow(number as u32 + 2);
+            params.push(value);
+        }
+        self.code.push(instruction);
+        self.code.extend(params);
+    }
+
+    fn cell(&mut self) -> usize {
+        self.rng.below(self.options.data_cells as u64) as usize
+    }
+
+    fn read_operand(&mut self) -> Operand {
+        match self.rng.below(3) {
+            0 => Operand::Position(self.cell()),
+            1 => Operand::Immediate(self.rng.range(-9, 10)),
+            _ => Operand::Relative(self.cell()),
+        }
+    }
+
+    fn write_operand(&mut self) -> Operand {
+        match self.rng.below(2) {
+            0 => Operand::Position(self.cell()),
+            _ => Operand::Relative(self.cell()),
+        }
+    }
+
+    /// Generate up to `length` statements at the nesting depth.
+    fn block(&mut self, depth: usize, length: usize) {
+        let relative_base = self.relative_base;
+        for _ in 0..length {
+            if self.budget == 0 {
+                break;
+            }
+            self.budget -= 1;
+            let nested = depth < self.options.max_depth;
+            match self.rng.below(12) {
+                0..=5 => {
+                    let opcode = [1, 2, 7, 8][self.rng.below(4) as usize];
+                    let operands = [
+                        self.read_operand(),
+                        self.read_operand(),
+                        self.write_operand(),
+                    ];
+                    self.emit(opcode, &operands);
+                }
+                6 => {
+                    let target = self.write_operand();
+                    self.emit(3, &[target]);
+                }
+                7 => {
+                    let value = self.read_operand();
+                    self.emit(4, &[value]);
+                }
+                8 => {
+                    let delta = self.rng.range(-3, 4);
+                    self.emit(9, &[Operand::Immediate(delta)]);
+                    self.relative_base += delta;
+                }
+                9 | 10 if nested => {
+                    // conditional jump over a block
+                    let opcode = 5 + self.rng.below(2) as i64;
+                    let condition = self.read_operand();
+                    self.emit(opcode, &[condition, Operand::Immediate(0)]);
+                    let target = self.code.len() - 1;
+                    let length = 1 + self.rng.below(4) as usize;
+                    self.block(depth + 1, length);
+                    self.code[target] = self.code.len() as i64;
+                }
+                11 if nested => {
+                    let counter = self.options.data_cells + self.counters;
+                    self.counters += 1;
+                    let iterations = self.rng.range(1, self.options.max_iterations + 1);
+                    let init = [
+                        Operand::Immediate(iterations),
+                        Operand::Immediate(0),
+                        Operand::Position(counter),
+                    ];
+                    self.emit(1, &init);
+                    let start = self.code.len() as i64;
+                    let length = 1 + self.rng.below(4) as usize;
+                    self.block(depth + 1, length);
+                    let decrement = [
+                        Operand::Position(counter),
+                        Operand::Immediate(-1),
+                        Operand::Position(counter),
+                    ];
+                    self.emit(1, &decrement);
+                    self.emit(5, &[Operand::Position(counter), Operand::Immediate(start)]);
+                }
+                _ => {
+                    let value = self.read_operand();
+                    self.emit(4, &[value]);
+                }
+            }
+        }
+        if self.relative_base != relative_base {
+            let delta = relative_base - self.relative_base;
+            self.emit(9, &[Operand::Immediate(delta)]);
+            self.relative_base = relative_base;
+        }
+    }
+}
+
+/// Generate a random, well-formed program, which terminates without ever leaving its memory.
+///
+/// The code is followed by the data cells, which hold small random values, and by the loop
+/// counters. Programs may still fail on arithmetic overflow, or wait for more input than given.
+///
+/// * `rng` - source of randomness
+/// * `options` - size and shape of the program
+pub fn generate(rng: &mut Rng, options: &FuzzOptions) -> Vec<i64> {
+    let mut generator = Generator {
+        rng,
+        options,
+        code: Vec::new(),
+        data_refs: Vec::new(),
+        counters: 0,
+        relative_base: 0,
+        budget: options.instructions,
+    };
+    // the relative base points to data cell 0; its address is only known at the end
+    generator.data_refs.push(1);
+    generator.emit(9, &[Operand::Immediate(0)]);
+    generator.block(0, options.instructions);
+    generator.emit(99, &[]);
+
+    let base = generator.code.len() as i64;
+    let mut program = generator.code;
+    for &position in &generator.data_refs {
+        program[position] += base;
+    }
+    for _ in 0..options.data_cells {
+        program.push(generator.rng.range(-9, 10));
+    }
+    program.resize(program.len() + generator.counters, 0);
+    program
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    Halted,
+    /// The program needs more input than given.
+    AwaitingInput,
+    /// The program failed at the instruction, e.g. on an invalid instruction, overflow or the
+    /// step limit.
+    Failed {
+        i_pointer: usize,
+    },
+}
+
+/// Observable result of a run, compared between the interpreters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub ending: Ending,
+    pub output: Vec<i128>,
+    /// Number of instructions executed
+    pub steps: u64,
+    /// Memory cells not zero at the end, by address
+    pub memory: BTreeMap<usize, i128>,
+}
+
+/// Run a program on a minimal Intcode interpreter with 64 bit words, which shares no code with
+/// `IntCodeComputer`, as reference for the differential tests.
+///
+/// * `program` - program to run
+/// * `input` - input values
+/// * `max_steps` - number of instructions, after which the run fails
+pub fn reference_run(program: &[i64], input: &[i64], max_steps: u64) -> Outcome {
+    let mut memory: BTreeMap<usize, i64> = program
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| value != 0)
+        .map(|(address, &value)| (address, value))
+        .collect();
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let (mut ip, mut rb, mut steps) = (0_usize, 0_i64, 0_u64);
+
+    let ending = loop {
+        if steps >= max_steps {
+            break Ending::Failed { i_pointer: ip };
+        }
+        let read = |memory: &BTreeMap<usize, i64>, address: usize| {
+            memory.get(&address).copied().unwrap_or(0)
+        };
+        let instruction = read(&memory, ip);
+        let mode = |number: u32| instruction / 10_i64.pow(number + 2) % 10;
+        let param = |number: u32| read(&memory, ip + 1 + number as usize);
+        let address = |number: u32| {
+            let offset = match mode(number) {
+                0 => 0,
+                2 => rb as i128,
+                _ => return None,
+            };
+            usize::try_from(offset + param(number) as i128).ok()
+        };
+        let arg = |number: u32| match mode(number) {
+            1 => Some(param(number)),
+            _ => address(number).map(|address| read(&memory, address)),
+        };
+
+        let result = if instruction < 0 {
+            None
+        } else {
+            match instruction % 100 {
+                opcode @ 1 | opcode @ 2 | opcode @ 7 | opcode @ 8 => (|| {
+                    let (a, b, target) = (arg(0)?, arg(1)?, address(2)?);
+                    let value = match opcode {
+                        1 => a.checked_add(b)?,
+                        2 => a.checked_mul(b)?,
+                        7 => (a < b) as i64,
+                        _ => (a == b) as i64,
+                    };
+                    Some((Some((target, value)), ip + 4, None))
+                })(),
+                3 => match address(0) {
+                    Some(target) => match input.next() {
+                        Some(&value) => Some((Some((target, value)), ip + 2, None)),
+                        None => break Ending::AwaitingInput,
+                    },
+                    None => None,
+                },
+                4 => arg(0).map(|value| (None, ip + 2, Some(value))),
+                opcode @ 5 | opcode @ 6 => (|| {
+                    let (condition, target) = (arg(0)?, arg(1)?);
+                    if (condition != 0) == (opcode == 5) {
+                        Some((None, usize::try_from(target).ok()?, None))
+                    } else {
+                        Some((None, ip + 3, None))
+                    }
+                })(),
+                9 => arg(0).and_then(|value| {
+                    rb = rb.checked_add(value)?;
+                    Some((None, ip + 2, None))
+                }),
+                99 => {
+                    steps += 1;
+                    break Ending::Halted;
+                }
+                _ => None,
+            }
+        };
+        match result {
+            Some((write, next, value)) => {
+                if let Some((address, value)) = write {
+                    memory.insert(address, value);
+                }
+                output.extend(value);
+                ip = next;
+                steps += 1;
+            }
+            None => break Ending::Failed { i_pointer: ip },
+        }
+    };
+
+    Outcome {
+        ending,
+        output: output.into_iter().map(i128::from).collect(),
+        steps,
+        memory: memory
+            .into_iter()
+            .filter(|&(_, value)| value != 0)
+            .map(|(address, value)| (address, value as i128))
+            .collect(),
+    }
+}
+
+fn outcome<W: Word>(
+    result: Result<RunState<W>, IntcodeError>,
+    output: Vec<W>,
+    steps: u64,
+    memory: &Memory<W>,
+) -> Outcome {
+    let ending = match result {
+        Ok(RunState::Halted) => Ending::Halted,
+        Ok(_) => Ending::AwaitingInput,
+        Err(err) => Ending::Failed {
+            i_pointer: err.i_pointer(),
+        },
+    };
+    let memory = memory
+        .pages()
+        .into_iter()
+        .flat_map(|(start, page)| {
+            page.iter()
+                .enumerate()
+                .map(move |(offset, &value)| (start + offset, value.to_i128()))
+        })
+        .filter(|&(_, value)| value != 0)
+        .collect();
+    Outcome {
+        ending,
+        output: output.into_iter().map(Word::to_i128).collect(),
+        steps,
+        memory,
+    }
+}
+
+fn computer<W: Word>(program: &[i64], input: &[i64], max_steps: u64) -> IntCodeComputer<W> {
+    let program: Vec<W> = program
+        .iter()
+        .map(|&v| W::from_i128(v as i128).unwrap())
+        .collect();
+    let mut icc = IntCodeComputer::new(&program);
+    icc.input
+        .extend(input.iter().map(|&v| W::from_i128(v as i128).unwrap()));
+    icc.set_max_steps(Some(max_steps));
+    icc
+}
+
+fn run_plain<W: Word>(mut icc: IntCodeComputer<W>) -> Outcome {
+    let result = icc.compute();
+    let output = icc.output.drain(..).collect();
+    outcome(result, output, icc.steps(), icc.memory())
+}
+
+/// Run as a coroutine, which continues on a fork of itself after every output.
+fn run_forked(mut icc: IntCodeComputer<i64>) -> Outcome {
+    let mut output = Vec::new();
+    let result = loop {
+        match icc.run() {
+            Ok(RunState::Output(value)) => {
+                output.push(value);
+                let mut fork = icc.fork();
+                fork.input = std::mem::take(&mut icc.input);
+                icc = fork;
+            }
+            result => break result,
+        }
+    };
+    outcome(result, output, icc.steps(), icc.memory())
+}
+
+/// Run up to the step, save and restore the computer, and run the restored one to the end.
+fn run_restored(mut icc: IntCodeComputer<i64>, split: u64, max_steps: u64) -> Outcome {
+    icc.set_max_steps(Some(split.min(max_steps)));
+    let result = icc.compute();
+    let mut output: Vec<i64> = icc.output.drain(..).collect();
+    match result {
+        Err(IntcodeError::StepLimitExceeded { .. }) if split < max_steps => {
+            let mut saved = Vec::new();
+            icc.save(&mut saved).unwrap();
+            let mut restored = IntCodeComputer::restore(io::Cursor::new(saved)).unwrap();
+            restored.set_max_steps(Some(max_steps));
+            let result = restored.compute();
+            output.extend(restored.output.drain(..));
+            outcome(result, output, restored.steps(), restored.memory())
+        }
+        result => outcome(result, output, icc.steps(), icc.memory()),
+    }
+}
+
+/// Run of a program on one of the interpreters
+type Run<'a> = Box<dyn Fn() -> Outcome + 'a>;
+
+/// Interpreter that disagrees with the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Name of the interpreter
+    pub engine: &'static str,
+    /// Outcome of the reference interpreter
+    pub expected: Outcome,
+    pub actual: Outcome,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} disagrees with the reference", self.engine)?;
+        writeln!(f, "expected: {:?}", self.expected)?;
+        write!(f, "actual:   {:?}", self.actual)
+    }
+}
+
+/// Run a program on all interpreters, and return the first one disagreeing with the reference.
+///
+/// The interpreters are `IntCodeComputer` as is (`plain`), with decode cache (`cached`), with
+/// profiling and tracing (`instrumented`), run as coroutine on a new fork after every output
+/// (`forked`), saved and restored halfway (`restored`), and with 128 bit words (`wide`). The
+/// latter is skipped for failing runs, as it does not overflow where 64 bit words do.
+///
+/// * `program` - program to run
+/// * `input` - input values
+/// * `max_steps` - number of instructions, after which a run fails
+pub fn check(program: &[i64], input: &[i64], max_steps: u64) -> Option<Mismatch> {
+    let expected = reference_run(program, input, max_steps);
+    let new = || computer::<i64>(program, input, max_steps);
+    let split = expected.steps / 2;
+    let mut runs: Vec<(&'static str, Run)> = vec![
+        ("plain", Box::new(|| run_plain(new()))),
+        (
+            "cached",
+            Box::new(|| {
+                let mut icc = new();
+                icc.set_decode_cache(true);
+                run_plain(icc)
+            }),
+        ),
+        (
+            "instrumented",
+            Box::new(|| {
+                let mut icc = new();
+                icc.set_profiling(true);
+                icc.set_trace(Some(Tracer::new(io::sink(), TraceFilter::All)));
+                run_plain(icc)
+            }),
+        ),
+        ("forked", Box::new(|| run_forked(new()))),
+        (
+            "restored",
+            Box::new(move || run_restored(new(), split, max_steps)),
+        ),
+    ];
+    if let Ending::Halted | Ending::AwaitingInput = expected.ending {
+        runs.push((
+            "wide",
+            Box::new(|| run_plain(computer::<i128>(program, input, max_steps))),
+        ));
+    }
+    for (engine, run) in runs {
+        let actual = run();
+        if actual != expected {
+            return Some(Mismatch {
+                engine,
+                expected,
+                actual,
+            });
+        }
+    }
+    None
+}
+
+/// Shrink a program, as long as it keeps failing.
+///
+/// Drops the longest prefix possible, as programs start at address zero, removes ever smaller
+/// chunks of words, then moves the remaining words towards zero, and repeats until no single
+/// word can be removed or made smaller. The result need not be a
+/// well-formed program anymore, so the check must cope with any program.
+///
+/// * `program` - failing program
+/// * `fails` - whether a program still fails
+pub fn shrink<F: Fn(&[i64]) -> bool>(program: &[i64], fails: F) -> Vec<i64> {
+    let mut program = program.to_vec();
+    loop {
+        let mut shrunk = false;
+
+        if let Some(start) = (1..program.len())
+            .rev()
+            .find(|&start| fails(&program[start..]))
+        {
+            program.drain(..start);
+            shrunk = true;
+        }
+
+        let mut size = program.len().div_ceil(2);
+        while size > 0 {
+            let mut start = 0;
+            while start + size <= program.len() {
+                let mut candidate = program.clone();
+                candidate.drain(start..start + size);
+                if fails(&candidate) {
+                    program = candidate;
+                    shrunk = true;
+                } else {
+                    start += size;
+                }
+            }
+            size /= 2;
+        }
+
+        for position in 0..program.len() {
+            let value = program[position];
+            for smaller in [0, value / 2, value - value.signum()] {
+                if smaller == value {
+                    continue;
+                }
+                let mut candidate = program.clone();
+                candidate[position] = smaller;
+                if fails(&candidate) {
+                    program = candidate;
+                    shrunk = true;
+                    break;
+                }
+            }
+        }
+
+        if !shrunk {
+            return program;
+        }
+    }
+}
+
+/// Program, on which the interpreters disagree, shrunk to a minimal reproduction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Number of the run, starting at zero
+    pub run: u64,
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    /// Disagreement on the shrunk program
+    pub mismatch: Mismatch,
+}
+
+/// Check random programs on all interpreters (see `check`), and shrink the first failing one.
+///
+/// Returns the first failure, if any.
+///
+/// * `seed` - seed of the random programs and inputs
+/// * `runs` - number of programs to check
+/// * `options` - shape of the programs
+pub fn fuzz(seed: u64, runs: u64, options: &FuzzOptions) -> Option<Failure> {
+    let mut rng = Rng::new(seed);
+    for run in 0..runs {
+        let program = generate(&mut rng, options);
+        let input: Vec<i64> = (0..options.inputs).map(|_| rng.range(-9, 10)).collect();
+        if check(&program, &input, options.max_steps).is_none() {
+            continue;
+        }
+        let program = shrink(&program, |program| {
+            check(program, &input, options.max_steps).is_some()
+        });
+        let mismatch = check(&program, &input, options.max_steps).unwrap();
+        return Some(Failure {
+            run,
+            program,
+            input,
+            mismatch,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_fuzz::{check, fuzz, generate, reference_run, shrink};
+    use crate::intcode_fuzz::{Ending, FuzzOptions, Rng};
+
+    #[test]
+    fn generated_programs_terminate() {
+        let options = FuzzOptions::default();
+        let mut rng = Rng::new(7);
+        let mut outputs = 0;
+        for _ in 0..200 {
+            let program = generate(&mut rng, &options);
+            let outcome = reference_run(&program, &[1, 2, 3, 4], options.max_steps);
+            assert!(outcome.steps < options.max_steps, "{:?}", program);
+            // all addresses lie within the program, apart from overflows
+            if let Ending::Failed { i_pointer } = outcome.ending {
+                assert!(i_pointer < program.len());
+            }
+            assert!(outcome
+                .memory
+                .keys()
+                .all(|&address| address < program.len()));
+            outputs += outcome.output.len();
+        }
+        assert!(outputs > 200);
+    }
+
+    #[test]
+    fn engines_agree() {
+        assert_eq!(None, fuzz(1, 300, &FuzzOptions::default()));
+    }
+
+    #[test]
+    fn engines_agree_on_failures() {
+        let max_steps = 1000;
+        for program in [
+            &[1101, 1, 2, 5, 99, 0][..],
+            &[3, 3, 99, 0],
+            &[1, -1, 0, 0, 99],
+            &[11101, 1, 2, 5, 99, 0],
+            &[1301, 1, 2, 5, 99, 0],
+            &[1102, i64::MAX, 2, 5, 99, 0],
+            &[109, i64::MAX, 109, 1, 99],
+            &[1106, 0, -5],
+            &[1105, 1, 0],
+            &[1105, 1, 1 << 40],
+            &[1105, 1, i64::MAX],
+            // an output in the last words of the memory the words can address
+            &[1101, 104, 0, i64::MAX - 1, 1105, 1, i64::MAX - 1],
+            // writes far away relative to the base, and outputs the value read back
+            &[109, 1 << 40, 21101, 1, 2, 0, 204, 0, 99],
+            &[4, 1000, 104, 7, 77],
+            &[-1],
+        ] {
+            assert_eq!(None, check(program, &[], max_steps), "{:?}", program);
+        }
+        assert_eq!(
+            Ending::Failed { i_pointer: 0 },
+            reference_run(&[1105, 1, 0], &[], max_steps).ending
+        );
+        assert_eq!(
+            Ending::AwaitingInput,
+            reference_run(&[3, 3, 99, 0], &[], max_steps).ending
+        );
+    }
+
+    #[test]
+    fn shrinking() {
+        let program = [5, 7, 3, 8, 9, 1, 2];
+        let contains_7_9 = |program: &[i64]| {
+            let seven = program.iter().position(|&v| v == 7);
+            let nine = program.iter().rposition(|&v| v == 9);
+            matches!((seven, nine), (Some(seven), Some(nine)) if seven < nine)
+        };
+        assert_eq!(vec![7, 9], shrink(&program, contains_7_9));
+
+        // values shrink towards zero
+        let program = [1, 1000, 2];
+        assert_eq!(vec![600], shrink(&program, |p| p.iter().any(|&v| v >= 600)));
+    }
+}
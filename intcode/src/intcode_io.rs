@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::mpsc;
+
+/// Result of reading from an `InputDevice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input<W> {
+    /// The next input value.
+    Value(W),
+    /// No value is available right now, but there may be one later.
+    Empty,
+    /// No value is available and there will never be one.
+    Disconnected,
+}
+
+/// Error returned by an `OutputDevice`, which cannot take any more values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+/// Source of input values for the Intcode computer.
+pub trait InputDevice<W> {
+    /// Read the next input value.
+    ///
+    /// * `block` - wait for a value, if none is available yet. Devices, which cannot wait, return
+    ///   `Input::Empty` instead.
+    fn read(&mut self, block: bool) -> Input<W>;
+
+    /// Values, which are available, but not read yet, in the order they will be read.
+    ///
+    /// Used to save the pending input along with the state of the computer. Devices, which cannot
+    /// look ahead (e.g. channels), return none.
+    fn pending(&self) -> Vec<W> {
+        Vec::new()
+    }
+}
+
+/// Sink for output values of the Intcode computer.
+pub trait OutputDevice<W> {
+    /// Write an output value.
+    fn write(&mut self, value: W) -> Result<(), Disconnected>;
+}
+
+impl<W, D: InputDevice<W> + ?Sized> InputDevice<W> for Box<D> {
+    fn read(&mut self, block: bool) -> Input<W> {
+        (**self).read(block)
+    }
+
+    fn pending(&self) -> Vec<W> {
+        (**self).pending()
+    }
+}
+
+impl<W, D: OutputDevice<W> + ?Sized> OutputDevice<W> for Box<D> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        (**self).write(value)
+    }
+}
+
+impl<W, D: InputDevice<W> + ?Sized> InputDevice<W> for &mut D {
+    fn read(&mut self, block: bool) -> Input<W> {
+        (**self).read(block)
+    }
+
+    fn pending(&self) -> Vec<W> {
+        (**self).pending()
+    }
+}
+
+impl<W, D: OutputDevice<W> + ?Sized> OutputDevice<W> for &mut D {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        (**self).write(value)
+    }
+}
+
+/// In-memory queue: input values are taken from the front, output values are added at the back.
+impl<W: Clone> InputDevice<W> for VecDeque<W> {
+    fn read(&mut self, _block: bool) -> Input<W> {
+        match self.pop_front() {
+            Some(value) => Input::Value(value),
+            None => Input::Empty,
+        }
+    }
+
+    fn pending(&self) -> Vec<W> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<W> OutputDevice<W> for VecDeque<W> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl<W> OutputDevice<W> for Vec<W> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+/// Input from a channel, e.g. for computers running in their own threads.
+impl<W> InputDevice<W> for mpsc::Receiver<W> {
+    fn read(&mut self, block: bool) -> Input<W> {
+        if block {
+            match self.recv() {
+                Ok(value) => Input::Value(value),
+                Err(_) => Input::Disconnected,
+            }
+        } else {
+            match self.try_recv() {
+                Ok(value) => Input::Value(value),
+                Err(mpsc::TryRecvError::Empty) => Input::Empty,
+                Err(mpsc::TryRecvError::Disconnected) => Input::Disconnected,
+            }
+        }
+    }
+}
+
+impl<W> OutputDevice<W> for mpsc::Sender<W> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        self.send(value).map_err(|_| Disconnected)
+    }
+}
+
+/// Input from a closure, which returns `None` if no value is available.
+pub struct FnInput<F>(pub F);
+
+impl<W, F: FnMut() -> Option<W>> InputDevice<W> for FnInput<F> {
+    fn read(&mut self, _block: bool) -> Input<W> {
+        match (self.0)() {
+            Some(value) => Input::Value(value),
+            None => Input::Empty,
+        }
+    }
+}
+
+impl<F> fmt::Debug for FnInput<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnInput")
+    }
+}
+
+/// Output to a closure.
+pub struct FnOutput<F>(pub F);
+
+impl<W, F: FnMut(W)> OutputDevice<W> for FnOutput<F> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+impl<F> fmt::Debug for FnOutput<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnOutput")
+    }
+}
+
+/// Input of one value per line of text, e.g. typed in on stdin.
+///
+/// Lines, which cannot be parsed, are reported on stderr and skipped. The end of the text
+/// disconnects the device.
+#[derive(Debug)]
+pub struct LineInput<R> {
+    reader: R,
+    prompt: Option<String>,
+}
+
+impl<R: BufRead> LineInput<R> {
+    /// * `reader` - text to read values from
+    /// * `prompt` - printed to stdout before reading a line
+    pub fn new(reader: R, prompt: Option<String>) -> LineInput<R> {
+        LineInput { reader, prompt }
+    }
+}
+
+impl LineInput<io::StdinLock<'static>> {
+    /// Read values from stdin, asking the user for every one.
+    pub fn stdin() -> LineInput<io::StdinLock<'static>> {
+        LineInput::new(
+            io::stdin().lock(),
+            Some("Please enter your input:".to_string()),
+        )
+    }
+}
+
+impl<W: FromStr, R: BufRead> InputDevice<W> for LineInput<R> {
+    fn read(&mut self, _block: bool) -> Input<W> {
+        loop {
+            if let Some(prompt) = &self.prompt {
+                println!("{}", prompt);
+            }
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return Input::Disconnected,
+                Ok(_) => {}
+            }
+            match line.trim().parse() {
+                Ok(value) => return Input::Value(value),
+                Err(_) => eprintln!("Not a valid input: {}", line.trim()),
+            }
+        }
+    }
+}
+
+/// Output of one value per line of text, e.g. printed to stdout.
+#[derive(Debug)]
+pub struct LineOutput<Wr> {
+    writer: Wr,
+}
+
+impl<Wr: Write> LineOutput<Wr> {
+    /// * `writer` - text sink for the values
+    pub fn new(writer: Wr) -> LineOutput<Wr> {
+        LineOutput { writer }
+    }
+}
+
+impl LineOutput<io::Stdout> {
+    /// Print values to stdout.
+    pub fn stdout() -> LineOutput<io::Stdout> {
+        LineOutput::new(io::stdout())
+    }
+}
+
+impl<W: fmt::Display, Wr: Write> OutputDevice<W> for LineOutput<Wr> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        writeln!(self.writer, "{}", value).map_err(|_| Disconnected)
+    }
+}
+
+/// Wrapper around another device, which records every value passing through.
+#[derive(Debug)]
+pub struct Recorder<D, W> {
+    pub inner: D,
+    pub log: Vec<W>,
+}
+
+impl<D, W> Recorder<D, W> {
+    /// * `inner` - device to be recorded
+    pub fn new(inner: D) -> Recorder<D, W> {
+        Recorder {
+            inner,
+            log: Vec::new(),
+        }
+    }
+}
+
+impl<W: Clone, D: InputDevice<W>> InputDevice<W> for Recorder<D, W> {
+    fn read(&mut self, block: bool) -> Input<W> {
+        let input = self.inner.read(block);
+        if let Input::Value(value) = &input {
+            self.log.push(value.clone());
+        }
+        input
+    }
+
+    fn pending(&self) -> Vec<W> {
+        self.inner.pending()
+    }
+}
+
+impl<W: Clone, D: OutputDevice<W>> OutputDevice<W> for Recorder<D, W> {
+    fn write(&mut self, value: W) -> Result<(), Disconnected> {
+        self.inner.write(value.clone())?;
+        self.log.push(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+
+    use crate::intcode_io::{
+        FnInput, FnOutput, Input, InputDevice, LineInput, LineOutput, OutputDevice, Recorder,
+    };
+
+    #[test]
+    fn queue() {
+        let mut queue = VecDeque::new();
+        queue.write(1).unwrap();
+        queue.write(2).unwrap();
+        assert_eq!(vec![1, 2], InputDevice::pending(&queue));
+        assert_eq!(Input::Value(1), queue.read(true));
+        assert_eq!(Input::Value(2), queue.read(true));
+        assert_eq!(Input::Empty, queue.read(true));
+    }
+
+    #[test]
+    fn closures() {
+        let mut count = 0;
+        let mut input = FnInput(|| {
+            count += 1;
+            if count <= 2 {
+                Some(count)
+            } else {
+                None
+            }
+        });
+        assert_eq!(Input::Value(1), input.read(false));
+        assert_eq!(Input::Value(2), input.read(false));
+        assert_eq!(Input::Empty, input.read(false));
+
+        let mut sum = 0;
+        let mut output = FnOutput(|value| sum += value);
+        output.write(3).unwrap();
+        output.write(4).unwrap();
+        assert_eq!(7, sum);
+    }
+
+    #[test]
+    fn lines() {
+        let mut input = LineInput::new(Cursor::new("1\nfoo\n -2 \n"), None);
+        assert_eq!(Input::Value(1), input.read(true));
+        assert_eq!(Input::Value(-2), input.read(true));
+        assert_eq!(Input::<i64>::Disconnected, input.read(true));
+
+        let mut output = LineOutput::new(Vec::new());
+        output.write(1).unwrap();
+        output.write(-2).unwrap();
+        assert_eq!(b"1\n-2\n".to_vec(), output.writer);
+    }
+
+    #[test]
+    fn recorder() {
+        let mut input = Recorder::new(VecDeque::from(vec![1, 2]));
+        input.read(true);
+        input.read(true);
+        input.read(true);
+        assert_eq!(vec![1, 2], input.log);
+
+        let mut output = Recorder::new(Vec::new());
+        output.write(3).unwrap();
+        assert_eq!(vec![3], output.log);
+        assert_eq!(vec![3], output.inner);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::intcode_mem::Memory;
+use crate::intcode_word::Word;
+
+/// Hash of a single memory cell. Cells holding zero hash to zero, so a cell, which was never
+/// written, and a cell, which was set to zero, are alike.
+fn cell_hash<W: Word>(address: usize, value: W) -> u64 {
+    let value = value.to_i128();
+    if value == 0 {
+        return 0;
+    }
+    // splitmix64 finaliser over address and both halves of the value
+    let mut x = (address as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (value as u64)
+        ^ ((value >> 64) as u64).rotate_left(32);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// State of the machine saved for comparison.
+#[derive(Debug, Clone)]
+struct Saved<W: Word> {
+    i_pointer: usize,
+    relative_base: W,
+    memory_hash: u64,
+    memory: Memory<W>,
+}
+
+/// Detector of infinite loops, i.e. of machine states, which repeat without I/O in between.
+///
+/// Follows Brent's cycle detection: the state is saved at steps, which are powers of two (counted
+/// from the last I/O), and every following state is compared to the saved one. A repeated state
+/// is found at the latest after twice the number of steps it takes to enter the loop and run
+/// through it once. The memory is compared by a hash, which is updated on every write, and only
+/// compared in full if pointers and hashes match. Saving the memory is cheap, as its pages are
+/// shared copy-on-write.
+#[derive(Debug, Clone)]
+pub(crate) struct LoopDetector<W: Word> {
+    memory_hash: u64,
+    saved: Option<Saved<W>>,
+    power: u64,
+    length: u64,
+}
+
+impl<W: Word> LoopDetector<W> {
+    /// * `memory` - current memory of the machine
+    pub fn new(memory: &Memory<W>) -> LoopDetector<W> {
+        let mut memory_hash = 0_u64;
+        for (start, content) in memory.pages() {
+            for (offset, &value) in content.iter().enumerate() {
+                memory_hash = memory_hash.wrapping_add(cell_hash(start + offset, value));
+            }
+        }
+        LoopDetector {
+            memory_hash,
+            saved: None,
+            power: 1,
+            length: 0,
+        }
+    }
+
+    /// Update the memory hash for a write.
+    pub fn write(&mut self, address: usize, old: W, new: W) {
+        self.memory_hash = self
+            .memory_hash
+            .wrapping_sub(cell_hash(address, old))
+            .wrapping_add(cell_hash(address, new));
+    }
+
+    /// Forget the saved state, e.g. after I/O, which makes states before incomparable.
+    pub fn reset(&mut self) {
+        self.saved = None;
+        self.power = 1;
+        self.length = 0;
+    }
+
+    /// Compare the state after an instruction to the saved state.
+    ///
+    /// Returns the period of the loop, if the state is repeated.
+    pub fn check(&mut self, i_pointer: usize, relative_base: W, memory: &Memory<W>) -> Option<u64> {
+        if let Some(saved) = &self.saved {
+            self.length += 1;
+            if saved.i_pointer == i_pointer
+                && saved.relative_base == relative_base
+                && saved.memory_hash == self.memory_hash
+                && saved.memory == *memory
+            {
+                return Some(self.length);
+            }
+        }
+        if self.saved.is_none() || self.length == self.power {
+            if self.saved.is_some() {
+                self.power *= 2;
+            }
+            self.length = 0;
+            self.saved = Some(Saved {
+                i_pointer,
+                relative_base,
+                memory_hash: self.memory_hash,
+                memory: memory.clone(),
+            });
+        }
+        None
+    }
+}
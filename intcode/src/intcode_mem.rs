@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::intcode_word::Word;
+
+pub(crate) const PAGE_BITS: usize = 10;
+
+/// Number of words per memory page
+pub const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+/// Number of pages, by which the contiguous part of the memory may grow for a single write
+pub(crate) const NEAR_PAGES: usize = 64;
+
+/// Pages are shared between clones of the memory, and copied on their first write.
+type Page<W> = Arc<[W; PAGE_SIZE]>;
+
+/// Unbounded, zero-initialised memory of the Intcode computer.
+///
+/// Memory is organised in pages of `PAGE_SIZE` words. Pages from address zero upwards are stored
+/// contiguously, and this part grows automatically if a write hits an address close to its end.
+/// Writes to addresses far beyond (e.g. to 10^9) go to a sparse map of pages, so only the pages
+/// actually touched are allocated. Reading a cell that was never written returns zero.
+///
+/// Cloning the memory is cheap: the clones share their pages until they write to them
+/// (copy-on-write), so only the pages actually modified are duplicated.
+#[derive(Debug, Clone)]
+pub struct Memory<W: Word> {
+    pages: Vec<Page<W>>,
+    far_pages: HashMap<usize, Page<W>>,
+}
+
+impl<W: Word> Memory<W> {
+    /// Create memory holding the program at address zero.
+    ///
+    /// * `program` - program instructions and data
+    pub fn new(program: &[W]) -> Memory<W> {
+        let pages = program
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [W::default(); PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+        Memory {
+            pages,
+            far_pages: HashMap::new(),
+        }
+    }
+
+    fn empty_page() -> Page<W> {
+        Arc::new([W::default(); PAGE_SIZE])
+    }
+
+    /// Read the value at `address`.
+    pub fn read(&self, address: usize) -> W {
+        let (page, offset) = (address >> PAGE_BITS, address % PAGE_SIZE);
+        match self.page(page) {
+            Some(page) => page[offset],
+            None => W::default(),
+        }
+    }
+
+    /// Write `value` to `address`, allocating memory as needed.
+    pub fn write(&mut self, address: usize, value: W) {
+        let (page, offset) = (address >> PAGE_BITS, address % PAGE_SIZE);
+        Arc::make_mut(self.page_mut(page))[offset] = value;
+    }
+
+    fn page(&self, page: usize) -> Option<&Page<W>> {
+        self.pages.get(page).or_else(|| self.far_pages.get(&page))
+    }
+
+    fn page_mut(&mut self, page: usize) -> &mut Page<W> {
+        // far pages lie beyond the contiguous part, as they are pulled into it when it grows
+        if page < self.pages.len() {
+            &mut self.pages[page]
+        } else if page < self.pages.len() + NEAR_PAGES && !self.far_pages.contains_key(&page) {
+            while self.pages.len() <= page {
+                // pull far pages into the contiguous part, once it has grown up to them
+                let next = self
+                    .far_pages
+                    .remove(&self.pages.len())
+                    .unwrap_or_else(Self::empty_page);
+                self.pages.push(next);
+            }
+            &mut self.pages[page]
+        } else {
+            self.far_pages.entry(page).or_insert_with(Self::empty_page)
+        }
+    }
+
+    /// Allocated pages as pairs of the address of their first word and their content, sorted by
+    /// address.
+    pub fn pages(&self) -> Vec<(usize, &[W])> {
+        let mut pages: Vec<(usize, &[W])> = self
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(page, content)| (page, &content[..]))
+            .chain(
+                self.far_pages
+                    .iter()
+                    .map(|(&page, content)| (page, &content[..])),
+            )
+            .map(|(page, content)| (page << PAGE_BITS, content))
+            .collect();
+        pages.sort_by_key(|&(address, _)| address);
+        pages
+    }
+
+    /// Number of words currently allocated, including pages shared with clones.
+    pub fn allocated(&self) -> usize {
+        (self.pages.len() + self.far_pages.len()) * PAGE_SIZE
+    }
+}
+
+/// Memories are equal if all their cells are, no matter which pages are allocated.
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Memory<W>) -> bool {
+        let pages = self.pages.len().max(other.pages.len());
+        let far_pages = self.far_pages.keys().chain(other.far_pages.keys());
+        (0..pages)
+            .chain(far_pages.copied())
+            .all(|page| match (self.page(page), other.page(page)) {
+                // pages shared by clones are equal without comparing them
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b) || a[..] == b[..],
+                (Some(a), None) | (None, Some(a)) => a.iter().all(|&v| v == W::default()),
+                (None, None) => true,
+            })
+    }
+}
+
+impl<W: Word> Eq for Memory<W> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_mem::{Memory, PAGE_SIZE};
+
+    #[test]
+    fn read_beyond_program() {
+        let memory = Memory::new(&[1, 2, 3]);
+        assert_eq!(3, memory.read(2));
+        assert_eq!(0, memory.read(3));
+        assert_eq!(0, memory.read(1_000_000_000));
+        assert_eq!(PAGE_SIZE, memory.allocated());
+    }
+
+    #[test]
+    fn grow_contiguous() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+        memory.write(3 * PAGE_SIZE + 5, 42);
+        assert_eq!(42, memory.read(3 * PAGE_SIZE + 5));
+        assert_eq!(0, memory.read(2 * PAGE_SIZE));
+        assert_eq!(1, memory.read(0));
+        assert_eq!(4 * PAGE_SIZE, memory.allocated());
+    }
+
+    #[test]
+    fn sparse_far_addresses() {
+        let mut memory: Memory<i64> = Memory::new(&[]);
+        memory.write(1_000_000_000, 7);
+        memory.write(1_000_000_001, 8);
+        memory.write(2_000_000_000, 9);
+        assert_eq!(7, memory.read(1_000_000_000));
+        assert_eq!(8, memory.read(1_000_000_001));
+        assert_eq!(9, memory.read(2_000_000_000));
+        assert_eq!(0, memory.read(1_500_000_000));
+        assert_eq!(2 * PAGE_SIZE, memory.allocated());
+    }
+
+    #[test]
+    fn far_pages_join_contiguous_part() {
+        let mut memory: Memory<i64> = Memory::new(&[]);
+        let far = 100 * PAGE_SIZE;
+        memory.write(far, 7);
+
+        // grow the contiguous part page by page until it reaches the far page
+        for page in 0..100 {
+            memory.write(page * PAGE_SIZE, 1);
+        }
+        assert_eq!(7, memory.read(far));
+        memory.write(far + 1, 8);
+        assert_eq!(7, memory.read(far));
+        assert_eq!(8, memory.read(far + 1));
+        assert_eq!(101 * PAGE_SIZE, memory.allocated());
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let mut memory: Memory<i64> = Memory::new(&[1, 2, 3]);
+        memory.write(PAGE_SIZE, 4);
+        let mut clone = memory.clone();
+        clone.write(1, 20);
+        memory.write(PAGE_SIZE, 40);
+
+        assert_eq!(2, memory.read(1));
+        assert_eq!(20, clone.read(1));
+        assert_eq!(40, memory.read(PAGE_SIZE));
+        assert_eq!(4, clone.read(PAGE_SIZE));
+    }
+
+    #[test]
+    fn equality() {
+        let mut memory: Memory<i64> = Memory::new(&[1, 2, 3]);
+        let mut other = memory.clone();
+        assert_eq!(memory, other);
+
+        // zeros written are equal to memory never written
+        other.write(1_000_000_000, 0);
+        other.write(3 * PAGE_SIZE, 0);
+        assert_eq!(memory, other);
+
+        memory.write(1_000_000_000, 5);
+        assert_ne!(memory, other);
+        other.write(1_000_000_000, 5);
+        assert_eq!(memory, other);
+    }
+}
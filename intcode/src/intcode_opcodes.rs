@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::intcode_disasm::op_info;
+use crate::intcode_word::Word;
+
+/// Maximum number of parameters of a custom instruction, as many as the modes the decoder reads
+pub const MAX_PARAMS: usize = 3;
+
+/// Role of a parameter of a custom instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    /// The operand is read, in position, immediate or relative mode.
+    Read,
+    /// The operand is the address written to, in position or relative mode.
+    Write,
+}
+
+/// Effect of a custom instruction, as returned by its handler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effect<W> {
+    /// Values to write, one for every `Param::Write` in the order of the parameters
+    pub writes: Vec<W>,
+    /// Address to continue at, instead of the instruction behind
+    pub jump: Option<usize>,
+    /// Value handed over as output, like the output instruction does
+    pub output: Option<W>,
+    /// Whether the program halts, like with the halt instruction (after handing over the output)
+    pub halt: bool,
+}
+
+impl<W> Effect<W> {
+    /// Write the values, and continue with the next instruction.
+    pub fn writes(writes: Vec<W>) -> Effect<W> {
+        Effect {
+            writes,
+            jump: None,
+            output: None,
+            halt: false,
+        }
+    }
+}
+
+type Handler<W> = dyn Fn(&[W]) -> Result<Effect<W>, String> + Send + Sync;
+
+/// Custom instruction: its parameters and the handler executing it.
+#[derive(Clone)]
+pub struct CustomOp<W> {
+    pub mnemonic: String,
+    pub params: Vec<Param>,
+    handler: Arc<Handler<W>>,
+}
+
+impl<W: Word> CustomOp<W> {
+    /// Create a custom instruction.
+    ///
+    /// * `mnemonic` - name of the instruction, e.g. for error messages
+    /// * `params` - role of every parameter
+    /// * `handler` - gets the values of the `Param::Read` parameters, after resolving their
+    ///   modes, and returns the effect of the instruction, or an error message. Handlers must be
+    ///   `Send` and `Sync`, so computers with custom instructions can run in their own threads.
+    pub fn new<F>(mnemonic: &str, params: &[Param], handler: F) -> CustomOp<W>
+    where
+        F: Fn(&[W]) -> Result<Effect<W>, String> + Send + Sync + 'static,
+    {
+        CustomOp {
+            mnemonic: mnemonic.to_string(),
+            params: params.to_vec(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Run the handler on the values read.
+    pub(crate) fn call(&self, args: &[W]) -> Result<Effect<W>, String> {
+        (self.handler)(args)
+    }
+
+    /// Number of parameters written to.
+    pub fn writes(&self) -> usize {
+        self.params.iter().filter(|&&p| p == Param::Write).count()
+    }
+}
+
+impl<W> fmt::Debug for CustomOp<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomOp")
+            .field("mnemonic", &self.mnemonic)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+/// Reason why a custom instruction cannot be registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryError {
+    /// Opcodes have two digits, so they range from 0 to 99.
+    InvalidOpcode(u8),
+    /// The opcode belongs to a built-in instruction, see `OpcodeRegistry::register_override`.
+    Builtin(u8),
+    /// A custom instruction is registered with the opcode already.
+    AlreadyRegistered(u8),
+    /// The instruction has more parameters than `MAX_PARAMS`.
+    TooManyParams { opcode: u8, params: usize },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::InvalidOpcode(opcode) => write!(f, "invalid opcode {}", opcode),
+            RegistryError::Builtin(opcode) => {
+                write!(f, "opcode {} is a built-in instruction", opcode)
+            }
+            RegistryError::AlreadyRegistered(opcode) => {
+                write!(f, "opcode {} is registered already", opcode)
+            }
+            RegistryError::TooManyParams { opcode, params } => write!(
+                f,
+                "opcode {} has {} parameters, at most {} are supported",
+                opcode, params, MAX_PARAMS
+            ),
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+/// Custom instructions by their opcode, see `IntCodeComputer::set_opcodes`.
+///
+/// Custom instructions are decoded like the built-in ones: the two lowest digits are the opcode,
+/// and the digits above are the modes of the parameters. Parameters read are resolved before the
+/// handler is called, and its values are written to the parameters written to.
+///
+/// ```
+/// use intcode::intcode_cmp::IntCodeComputer;
+/// use intcode::intcode_opcodes::{CustomOp, Effect, OpcodeRegistry, Param};
+///
+/// // square [a] into [b]
+/// let square = CustomOp::new("sqr", &[Param::Read, Param::Write], |args: &[i64]| {
+///     Ok(Effect::writes(vec![args[0] * args[0]]))
+/// });
+/// let mut registry = OpcodeRegistry::new();
+/// registry.register(42, square).unwrap();
+///
+/// let program = [3, 9, 42, 9, 9, 4, 9, 99, 0, 0];
+/// let mut icc = IntCodeComputer::new(&program);
+/// icc.set_opcodes(Some(registry));
+/// icc.input.push_back(12);
+/// icc.compute().unwrap();
+///
+/// assert_eq!(Some(144), icc.output.pop_front());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpcodeRegistry<W> {
+    // shared, so the computer can hold on to an instruction while executing it
+    ops: BTreeMap<u8, Arc<CustomOp<W>>>,
+}
+
+impl<W: Word> OpcodeRegistry<W> {
+    pub fn new() -> OpcodeRegistry<W> {
+        OpcodeRegistry {
+            ops: BTreeMap::new(),
+        }
+    }
+
+    fn check(opcode: u8, op: &CustomOp<W>) -> Result<(), RegistryError> {
+        if opcode > 99 {
+            return Err(RegistryError::InvalidOpcode(opcode));
+        }
+        if op.params.len() > MAX_PARAMS {
+            return Err(RegistryError::TooManyParams {
+                opcode,
+                params: op.params.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Register a custom instruction with an opcode, which is neither built-in nor registered.
+    ///
+    /// * `opcode` - opcode of the instruction
+    /// * `op` - parameters and handler of the instruction
+    pub fn register(&mut self, opcode: u8, op: CustomOp<W>) -> Result<(), RegistryError> {
+        Self::check(opcode, &op)?;
+        if op_info(opcode as i128).is_some() {
+            return Err(RegistryError::Builtin(opcode));
+        }
+        if self.ops.contains_key(&opcode) {
+            return Err(RegistryError::AlreadyRegistered(opcode));
+        }
+        self.ops.insert(opcode, Arc::new(op));
+        Ok(())
+    }
+
+    /// Register a custom instruction, replacing a built-in or registered instruction with the
+    /// opcode.
+    ///
+    /// The disassembler, tracer and debugger still describe built-in opcodes as built-in.
+    ///
+    /// * `opcode` - opcode of the instruction
+    /// * `op` - parameters and handler of the instruction
+    pub fn register_override(&mut self, opcode: u8, op: CustomOp<W>) -> Result<(), RegistryError> {
+        Self::check(opcode, &op)?;
+        self.ops.insert(opcode, Arc::new(op));
+        Ok(())
+    }
+
+    /// The custom instruction with the opcode, if any.
+    pub fn get(&self, opcode: u8) -> Option<&CustomOp<W>> {
+        self.ops.get(&opcode).map(|op| &**op)
+    }
+
+    pub(crate) fn get_shared(&self, opcode: u8) -> Option<Arc<CustomOp<W>>> {
+        self.ops.get(&opcode).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::intcode_cmp::{IntCodeComputer, RunState};
+    use crate::intcode_error::IntcodeError;
+    use crate::intcode_opcodes::{CustomOp, Effect, OpcodeRegistry, Param, RegistryError};
+
+    fn nop() -> CustomOp<i64> {
+        CustomOp::new("nop", &[], |_| Ok(Effect::default()))
+    }
+
+    #[test]
+    fn registration() {
+        let mut registry = OpcodeRegistry::new();
+        assert_eq!(Ok(()), registry.register(50, nop()));
+        assert_eq!(
+            Err(RegistryError::AlreadyRegistered(50)),
+            registry.register(50, nop())
+        );
+        assert_eq!(Err(RegistryError::Builtin(4)), registry.register(4, nop()));
+        assert_eq!(
+            Err(RegistryError::InvalidOpcode(100)),
+            registry.register(100, nop())
+        );
+        let wide = CustomOp::new("wide", &[Param::Read; 4], |_| Ok(Effect::default()));
+        assert_eq!(
+            Err(RegistryError::TooManyParams {
+                opcode: 51,
+                params: 4
+            }),
+            registry.register(51, wide)
+        );
+
+        assert_eq!(Ok(()), registry.register_override(4, nop()));
+        assert_eq!(Ok(()), registry.register_override(50, nop()));
+        assert_eq!("nop", registry.get(4).unwrap().mnemonic);
+        assert!(registry.get(51).is_none());
+    }
+
+    #[test]
+    fn parameter_modes() {
+        // print the operands of 60 to the host, then store their sum with 61
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let sink = printed.clone();
+        let print = CustomOp::new("dbg", &[Param::Read, Param::Read], move |args: &[i64]| {
+            sink.lock().unwrap().extend_from_slice(args);
+            Ok(Effect::default())
+        });
+        let sum = CustomOp::new(
+            "sum",
+            &[Param::Read, Param::Write, Param::Read],
+            |args: &[i64]| Ok(Effect::writes(vec![args[0] + args[1]])),
+        );
+        let mut registry = OpcodeRegistry::new();
+        registry.register(60, print).unwrap();
+        registry.register(61, sum).unwrap();
+
+        // rb = 13, dbg [13], #7, sum rb+0, rb-1, #1, out [12]
+        let program = [109, 13, 1060, 13, 7, 12261, 0, -1, 1, 4, 12, 99, 0, 30];
+        let mut icc = IntCodeComputer::new(&program);
+        icc.set_opcodes(Some(registry));
+        icc.compute().unwrap();
+
+        assert_eq!(vec![30, 7], *printed.lock().unwrap());
+        assert_eq!(Some(31), icc.output.pop_front());
+    }
+
+    #[test]
+    fn control_flow() {
+        // 70 jumps to its operand, 71 outputs its operand and halts
+        let jump = CustomOp::new("jmp", &[Param::Read], |args: &[i64]| {
+            Ok(Effect {
+                jump: Some(args[0] as usize),
+                ..Effect::default()
+            })
+        });
+        let exit = CustomOp::new("exit", &[Param::Read], |args: &[i64]| {
+            Ok(Effect {
+                output: Some(args[0]),
+                halt: true,
+                ..Effect::default()
+            })
+        });
+        let mut registry = OpcodeRegistry::new();
+        registry.register(70, jump).unwrap();
+        registry.register(71, exit).unwrap();
+
+        let program = [170, 4, 171, 1, 171, 2];
+        let mut icc = IntCodeComputer::new(&program);
+        icc.set_opcodes(Some(registry));
+        assert_eq!(RunState::Output(2), icc.run().unwrap());
+        assert_eq!(RunState::Halted, icc.run().unwrap());
+        assert!(icc.finished());
+    }
+
+    #[test]
+    fn override_builtin() {
+        // output twice the value
+        let double = CustomOp::new("out2", &[Param::Read], |args: &[i64]| {
+            Ok(Effect {
+                output: Some(2 * args[0]),
+                ..Effect::default()
+            })
+        });
+        let mut registry = OpcodeRegistry::new();
+        registry.register_override(4, double).unwrap();
+
+        let program = [104, 21, 99];
+        let mut icc = IntCodeComputer::new(&program);
+        icc.compute().unwrap();
+        assert_eq!(Some(21), icc.output.pop_front());
+
+        let mut icc = IntCodeComputer::new(&program);
+        icc.set_opcodes(Some(registry));
+        icc.compute().unwrap();
+        assert_eq!(Some(42), icc.output.pop_front());
+    }
+
+    #[test]
+    fn failures() {
+        let fail = CustomOp::new("fail", &[Param::Read], |args: &[i64]| match args[0] {
+            0 => Err("zero".to_string()),
+            _ => Ok(Effect::default()),
+        });
+        let mut registry = OpcodeRegistry::new();
+        registry.register(80, fail).unwrap();
+        registry.register(81, nop()).unwrap();
+        registry
+            .register(
+                82,
+                CustomOp::new("set", &[Param::Write], |_| Ok(Effect::default())),
+            )
+            .unwrap();
+
+        let run = |program: &[i64]| {
+            let mut icc = IntCodeComputer::new(program);
+            icc.set_opcodes(Some(registry.clone()));
+            icc.compute()
+        };
+        assert_eq!(Ok(RunState::Halted), run(&[180, 1, 81, 99]));
+        assert_eq!(
+            Err(IntcodeError::CustomInstructionFailed {
+                i_pointer: 0,
+                instruction: 180,
+                message: "zero".to_string()
+            }),
+            run(&[180, 0, 99])
+        );
+        assert_eq!(
+            Err(IntcodeError::CustomInstructionFailed {
+                i_pointer: 0,
+                instruction: 82,
+                message: "set returned 0 values for 1 parameters written to".to_string()
+            }),
+            run(&[82, 3, 99, 0])
+        );
+        assert_eq!(
+            Err(IntcodeError::WriteInImmediateMode {
+                i_pointer: 0,
+                instruction: 182,
+                param: 0
+            }),
+            run(&[182, 3, 99, 0])
+        );
+        assert_eq!(
+            Err(IntcodeError::UnknownOpcode {
+                i_pointer: 0,
+                instruction: 83
+            }),
+            run(&[83, 99])
+        );
+    }
+}
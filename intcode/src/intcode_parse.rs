@@ -0,0 +1,222 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::intcode_word::Word;
+
+/// Error while loading a program.
+#[derive(Debug)]
+pub enum ProgramError {
+    Io(io::Error),
+    /// A token is not a number of the word type; `offset` counts bytes from the start of the text.
+    InvalidToken {
+        offset: usize,
+        token: String,
+    },
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramError::Io(err) => write!(f, "cannot read program: {}", err),
+            ProgramError::InvalidToken { offset, token } => {
+                write!(f, "invalid number {:?} at offset {}", token, offset)
+            }
+        }
+    }
+}
+
+impl Error for ProgramError {}
+
+impl From<io::Error> for ProgramError {
+    fn from(err: io::Error) -> ProgramError {
+        ProgramError::Io(err)
+    }
+}
+
+/// Error in the arguments of a tool, which takes `<program-file> [entry-address...]`. Displayed
+/// as the message for the user.
+#[derive(Debug)]
+pub enum ArgsError {
+    /// The program file is missing; holds the name of the tool.
+    Usage(String),
+    /// The program file cannot be loaded.
+    Program { path: String, error: ProgramError },
+    /// An entry address is not a valid address.
+    InvalidEntry(String),
+}
+
+impl ArgsError {
+    /// Exit code of the tool: 1 if the program cannot be loaded, else 2.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ArgsError::Program { .. } => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::Usage(tool) => {
+                write!(f, "Usage: {} <program-file> [entry-address...]", tool)
+            }
+            ArgsError::Program { path, error } => write!(f, "Cannot load {}: {}", path, error),
+            ArgsError::InvalidEntry(arg) => write!(f, "Not a valid entry address: {}", arg),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+/// Parse a program in the text format of the puzzles, i.e. comma-separated numbers.
+///
+/// Whitespace around the numbers (including a trailing newline) is ignored. Empty text gives an
+/// empty program.
+///
+/// ```
+/// use intcode::intcode_parse::parse_program;
+///
+/// let program: Vec<i64> = parse_program("1,0,0,3,\n99, -1\n").unwrap();
+/// assert_eq!(vec![1, 0, 0, 3, 99, -1], program);
+///
+/// let error = parse_program::<i64>("1,0,0,3,\n9 9").unwrap_err();
+/// assert_eq!("invalid number \"9 9\" at offset 9", error.to_string());
+/// ```
+pub fn parse_program<W: Word>(text: &str) -> Result<Vec<W>, ProgramError> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut program = Vec::new();
+    let mut start = 0;
+    for token in text.split(',') {
+        let offset = start + (token.len() - token.trim_start().len());
+        let trimmed = token.trim();
+        let value = trimmed.parse().map_err(|_| ProgramError::InvalidToken {
+            offset,
+            token: trimmed.to_string(),
+        })?;
+        program.push(value);
+        start += token.len() + 1;
+    }
+    Ok(program)
+}
+
+/// Read a program in the text format of the puzzles, e.g. from stdin.
+///
+/// * `reader` - source of the program text
+pub fn read_program<W: Word, R: Read>(mut reader: R) -> Result<Vec<W>, ProgramError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    parse_program(&text)
+}
+
+/// Load a program in the text format of the puzzles from a file.
+///
+/// * `path` - path of the file, or `-` for stdin
+pub fn load_program<W: Word, P: AsRef<Path>>(path: P) -> Result<Vec<W>, ProgramError> {
+    let path = path.as_ref();
+    if path == Path::new("-") {
+        read_program(io::stdin())
+    } else {
+        read_program(File::open(path)?)
+    }
+}
+
+/// Load the program and the entry points given to a tool as `<program-file> [entry-address...]`,
+/// e.g. the disassembler.
+///
+/// Returns the program and the entry points, which start with address zero.
+///
+/// * `args` - command line arguments, starting with the name of the tool
+pub fn load_program_and_entries<W: Word>(
+    args: &[String],
+) -> Result<(Vec<W>, Vec<usize>), ArgsError> {
+    let tool = args.first().map_or("intcode", String::as_str);
+    let path = args
+        .get(1)
+        .ok_or_else(|| ArgsError::Usage(tool.to_string()))?;
+    let mut entries = vec![0];
+    for arg in &args[2..] {
+        entries.push(
+            arg.parse()
+                .map_err(|_| ArgsError::InvalidEntry(arg.to_string()))?,
+        );
+    }
+    let program = load_program(path).map_err(|error| ArgsError::Program {
+        path: path.to_string(),
+        error,
+    })?;
+    Ok((program, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_parse::{load_program_and_entries, ArgsError};
+    use crate::intcode_parse::{parse_program, read_program, ProgramError};
+
+    fn invalid_token(text: &str) -> Option<(usize, String)> {
+        match parse_program::<i32>(text) {
+            Err(ProgramError::InvalidToken { offset, token }) => Some((offset, token)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(
+            vec![1, 0, -7, 99],
+            parse_program::<i64>("1,0,-7,99").unwrap()
+        );
+        assert_eq!(
+            vec![1, 0, -7, 99],
+            parse_program::<i64>(" 1 ,\t0,\n-7,  99\r\n").unwrap()
+        );
+        assert_eq!(Vec::<i64>::new(), parse_program::<i64>("\n").unwrap());
+        assert_eq!(
+            vec![104, 1, 99],
+            read_program::<i64, _>(&b"104,1,99\n"[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        assert_eq!(Some((4, "x".to_string())), invalid_token("1,2,x,99"));
+        assert_eq!(Some((6, "3 4".to_string())), invalid_token("1,2,\n 3 4,99"));
+        assert_eq!(Some((4, "".to_string())), invalid_token("1,2,,99"));
+        assert_eq!(Some((6, "".to_string())), invalid_token("1,99,\n"));
+        // does not fit into i32
+        assert_eq!(
+            Some((0, "3000000000".to_string())),
+            invalid_token("3000000000,99")
+        );
+        assert_eq!(
+            "invalid number \"x\" at offset 4",
+            parse_program::<i32>("1,2,x,99").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn tool_args() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        let result = load_program_and_entries::<i64>(&args(&["intcode-disasm"]));
+        let err = result.unwrap_err();
+        assert_eq!(2, err.exit_code());
+        assert_eq!(
+            "Usage: intcode-disasm <program-file> [entry-address...]",
+            err.to_string()
+        );
+
+        let result = load_program_and_entries::<i64>(&args(&["intcode-cfg", "-", "4", "x"]));
+        assert!(matches!(result, Err(ArgsError::InvalidEntry(arg)) if arg == "x"));
+
+        let result = load_program_and_entries::<i64>(&args(&["intcode-cfg", "/nonexistent"]));
+        let err = result.unwrap_err();
+        assert_eq!(1, err.exit_code());
+        assert!(matches!(err, ArgsError::Program { path, .. } if path == "/nonexistent"));
+    }
+}
@@ -0,0 +1,309 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+use crate::intcode_disasm::{disassemble_from, op_info, Item};
+use crate::intcode_word::Word;
+
+/// Memory accesses of an instruction: the addresses of its operands read in position or relative
+/// mode, and the address it writes to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Accesses {
+    pub reads: Vec<usize>,
+    pub write: Option<usize>,
+}
+
+/// Loop found by its back-edge, i.e. a jump to an address not behind the jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotLoop {
+    /// Address of the jump instruction
+    pub from: usize,
+    /// Address jumped to, i.e. the start of the loop
+    pub to: usize,
+    /// Number of times the jump was taken
+    pub count: u64,
+}
+
+/// Execution profile of a program, see `IntCodeComputer::set_profiling`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Number of executions by address of the instruction
+    pub executions: BTreeMap<usize, u64>,
+    /// Number of executions by opcode
+    pub opcodes: BTreeMap<i128, u64>,
+    /// Mnemonics of the executed custom instructions by opcode, see `OpcodeRegistry`
+    pub custom: BTreeMap<i128, String>,
+    /// Number of operand reads by memory address
+    pub reads: BTreeMap<usize, u64>,
+    /// Number of writes by memory address
+    pub writes: BTreeMap<usize, u64>,
+    /// Number of taken jumps by address of the jump and target, for jumps backwards only
+    pub back_edges: BTreeMap<(usize, usize), u64>,
+}
+
+fn json_map<K: fmt::Display>(map: impl Iterator<Item = (K, u64)>) -> String {
+    let entries: Vec<String> = map
+        .map(|(key, count)| format!("\"{}\":{}", key, count))
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+impl Profile {
+    /// Record an executed instruction.
+    ///
+    /// * `i_pointer` - address of the instruction
+    /// * `opcode` - its opcode
+    /// * `custom` - mnemonic of the custom instruction, if the opcode is registered
+    /// * `accesses` - memory it read and wrote
+    /// * `next` - address of the next instruction
+    pub(crate) fn record(
+        &mut self,
+        i_pointer: usize,
+        opcode: i128,
+        custom: Option<&str>,
+        accesses: Accesses,
+        next: usize,
+    ) {
+        *self.executions.entry(i_pointer).or_insert(0) += 1;
+        *self.opcodes.entry(opcode).or_insert(0) += 1;
+        if let Some(mnemonic) = custom {
+            self.custom
+                .entry(opcode)
+                .or_insert_with(|| mnemonic.to_string());
+        }
+        for address in accesses.reads {
+            *self.reads.entry(address).or_insert(0) += 1;
+        }
+        if let Some(address) = accesses.write {
+            *self.writes.entry(address).or_insert(0) += 1;
+        }
+        if let 5 | 6 = opcode {
+            if next <= i_pointer {
+                *self.back_edges.entry((i_pointer, next)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Number of instructions executed.
+    pub fn steps(&self) -> u64 {
+        self.opcodes.values().sum()
+    }
+
+    /// Loops with the most iterations, most iterations first.
+    ///
+    /// * `count` - maximum number of loops
+    pub fn hottest_loops(&self, count: usize) -> Vec<HotLoop> {
+        let mut loops: Vec<HotLoop> = self
+            .back_edges
+            .iter()
+            .map(|(&(from, to), &count)| HotLoop { from, to, count })
+            .collect();
+        loops.sort_by_key(|hot| (Reverse(hot.count), hot.from, hot.to));
+        loops.truncate(count);
+        loops
+    }
+
+    /// Report as a text table: executions by opcode, hottest loops, hottest instructions and most
+    /// accessed memory cells.
+    ///
+    /// * `top` - number of entries in the lists of loops, instructions and cells
+    pub fn to_text(&self, top: usize) -> String {
+        let steps = self.steps();
+        let share = |count: u64| 100.0 * count as f64 / steps.max(1) as f64;
+        let mut text = format!("{} instructions executed\n", steps);
+
+        text += "\nopcode  mnemonic       count   share\n";
+        for (&opcode, &count) in &self.opcodes {
+            let mnemonic = match self.custom.get(&opcode) {
+                Some(mnemonic) => mnemonic.as_str(),
+                None => op_info(opcode).map_or("???", |op| op.mnemonic),
+            };
+            writeln!(
+                text,
+                "{:>6}  {:<8} {:>11} {:>6.1}%",
+                opcode,
+                mnemonic,
+                count,
+                share(count)
+            )
+            .unwrap();
+        }
+
+        text += "\nhottest loops\n  jump  target  iterations\n";
+        for hot in self.hottest_loops(top) {
+            writeln!(text, "{:>6}  {:>6}  {:>10}", hot.from, hot.to, hot.count).unwrap();
+        }
+
+        let mut hottest: Vec<(usize, u64)> =
+            self.executions.iter().map(|(&a, &c)| (a, c)).collect();
+        hottest.sort_by_key(|&(address, count)| (Reverse(count), address));
+        text += "\nhottest instructions\naddress  executions   share\n";
+        for &(address, count) in hottest.iter().take(top) {
+            writeln!(text, "{:>7}  {:>10} {:>6.1}%", address, count, share(count)).unwrap();
+        }
+
+        let cells: BTreeSet<usize> = self
+            .reads
+            .keys()
+            .chain(self.writes.keys())
+            .copied()
+            .collect();
+        let mut cells: Vec<(usize, u64, u64)> = cells
+            .into_iter()
+            .map(|address| {
+                let reads = self.reads.get(&address).copied().unwrap_or(0);
+                let writes = self.writes.get(&address).copied().unwrap_or(0);
+                (address, reads, writes)
+            })
+            .collect();
+        cells.sort_by_key(|&(address, reads, writes)| (Reverse(reads + writes), address));
+        text += "\nmost accessed memory\naddress       reads      writes\n";
+        for &(address, reads, writes) in cells.iter().take(top) {
+            writeln!(text, "{:>7} {:>11} {:>11}", address, reads, writes).unwrap();
+        }
+        text
+    }
+
+    /// Report as a single JSON object:
+    ///
+    /// `{"steps":7,"opcodes":{"1":2,...},"executions":{...},"reads":{...},"writes":{...},"loops":[...]}`
+    ///
+    /// Maps are keyed by opcode or address. All loops are listed, hottest first, as objects like
+    /// `{"from":6,"to":2,"count":1}`.
+    pub fn to_json(&self) -> String {
+        let loops: Vec<String> = self
+            .hottest_loops(self.back_edges.len())
+            .iter()
+            .map(|hot| {
+                format!(
+                    "{{\"from\":{},\"to\":{},\"count\":{}}}",
+                    hot.from, hot.to, hot.count
+                )
+            })
+            .collect();
+        format!(
+            "{{\"steps\":{},\"opcodes\":{},\"executions\":{},\"reads\":{},\"writes\":{},\"loops\":[{}]}}",
+            self.steps(),
+            json_map(self.opcodes.iter().map(|(&k, &v)| (k, v))),
+            json_map(self.executions.iter().map(|(&k, &v)| (k, v))),
+            json_map(self.reads.iter().map(|(&k, &v)| (k, v))),
+            json_map(self.writes.iter().map(|(&k, &v)| (k, v))),
+            loops.join(",")
+        )
+    }
+
+    /// Disassembler listing with the number of executions in front of every instruction.
+    ///
+    /// Every executed address is used as entry point, so code behind computed jumps is listed as
+    /// well. Instructions, which were never executed, are marked with `#####`, data with `-`.
+    /// Executed words, which are no valid instruction in `program` (as the program modified them
+    /// before), are listed as data with their number of executions.
+    ///
+    /// * `program` - the program, which was profiled
+    pub fn coverage<W: Word>(&self, program: &[W]) -> String {
+        let mut entries = vec![0];
+        entries.extend(self.executions.keys());
+        let mut text = String::new();
+        for line in disassemble_from(program, &entries).lines {
+            let count = match (&line.item, self.executions.get(&line.address)) {
+                (_, Some(count)) => count.to_string(),
+                (Item::Instruction(_), None) => "#####".to_string(),
+                (Item::Data(_), None) => "-".to_string(),
+            };
+            writeln!(text, "{:>10} {}", count, line).unwrap();
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode_cmp::IntCodeComputer;
+    use crate::intcode_opcodes::{CustomOp, Effect, OpcodeRegistry, Param};
+    use crate::intcode_profile::HotLoop;
+
+    // count [15] down from the input to zero, then skip the output of [15] and halt
+    const COUNTDOWN: [i64; 16] = [
+        3, 15, 1001, 15, -1, 15, 1005, 15, 2, 1006, 15, 14, 4, 15, 99, 0,
+    ];
+
+    #[test]
+    fn counts() {
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.set_profiling(true);
+        icc.input.push_back(3);
+        icc.compute().unwrap();
+
+        let profile = icc.profile().unwrap();
+        assert_eq!(icc.steps(), profile.steps());
+        assert_eq!(Some(&3), profile.executions.get(&2));
+        assert_eq!(Some(&1), profile.executions.get(&14));
+        assert_eq!(None, profile.executions.get(&12));
+        assert_eq!(Some(&3), profile.opcodes.get(&5));
+        assert_eq!(Some(&(3 + 3 + 1)), profile.reads.get(&15));
+        assert_eq!(Some(&(1 + 3)), profile.writes.get(&15));
+        assert_eq!(
+            vec![HotLoop {
+                from: 6,
+                to: 2,
+                count: 2
+            }],
+            profile.hottest_loops(5)
+        );
+    }
+
+    #[test]
+    fn reports() {
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.set_profiling(true);
+        icc.input.push_back(2);
+        icc.compute().unwrap();
+        let profile = icc.take_profile().unwrap();
+        assert!(icc.profile().is_none());
+
+        assert_eq!(
+            "{\"steps\":7,\"opcodes\":{\"1\":2,\"3\":1,\"5\":2,\"6\":1,\"99\":1},\
+             \"executions\":{\"0\":1,\"2\":2,\"6\":2,\"9\":1,\"14\":1},\
+             \"reads\":{\"15\":5},\"writes\":{\"15\":3},\"loops\":[{\"from\":6,\"to\":2,\"count\":1}]}",
+            profile.to_json()
+        );
+
+        let text = profile.to_text(3);
+        assert!(text.starts_with("7 instructions executed\n"));
+        assert!(text.contains("\n     5  jnz                2   28.6%\n"));
+        assert!(text.contains("\n     6       2           1\n"));
+
+        let expected = "         1     0: in [15]
+         2     2: add [15], #-1, [15]
+         2     6: jnz [15], #2
+         1     9: jz [15], #14
+     #####    12: out [15]
+         1    14: hlt
+         -    15: data 0
+";
+        assert_eq!(expected, profile.coverage(&COUNTDOWN));
+    }
+
+    #[test]
+    fn custom_opcodes() {
+        // the output instruction is replaced, opcode 50 is new
+        let double = CustomOp::new("out2", &[Param::Read], |args: &[i64]| {
+            Ok(Effect {
+                output: Some(2 * args[0]),
+                ..Effect::default()
+            })
+        });
+        let nop = CustomOp::new("nop", &[], |_| Ok(Effect::default()));
+        let mut registry = OpcodeRegistry::new();
+        registry.register_override(4, double).unwrap();
+        registry.register(50, nop).unwrap();
+
+        let mut icc = IntCodeComputer::new(&[50, 104, 21, 99]);
+        icc.set_opcodes(Some(registry));
+        icc.set_profiling(true);
+        icc.compute().unwrap();
+        let text = icc.profile().unwrap().to_text(3);
+        assert!(text.contains("\n     4  out2               1   33.3%\n"));
+        assert!(text.contains("\n    50  nop                1   33.3%\n"));
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::intcode_cmp::Overflow;
+use crate::intcode_mem::Memory;
+use crate::intcode_word::Word;
+
+/// Version of the snapshot format written by `Snapshot::write_to`.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const MAGIC: &str = "INTCODE-SNAPSHOT";
+
+/// Complete state of an Intcode computer, which can be saved to and restored from a file.
+///
+/// The file format (version 1) is plain text, one entry per line:
+///
+/// ```text
+/// INTCODE-SNAPSHOT 1
+/// word_bits 64
+/// i_pointer 2
+/// relative_base 0
+/// return_control true
+/// overflow checked
+/// steps 1
+/// max_steps none
+/// halted false
+/// input 7 8
+/// page 0 3,12,4,12,1001,12,-1,12,1005,12,2,99,5
+/// end
+/// ```
+///
+/// * The first line names the format and its version. Files of other versions are rejected.
+/// * `word_bits` is the size of the word type; it must match the computer restoring the file.
+/// * `overflow` is one of `wrapping`, `checked` or `panic`; `max_steps` is a number or `none`.
+/// * `input` lists the pending input values, i.e. values queued, but not read by the program.
+/// * Every `page` line holds the address of its first word and the comma-separated values of the
+///   following cells. Trailing zeros are left out, and memory not listed is zero.
+/// * `end` marks the end of the file, so truncated files are detected.
+///
+/// Output values, I/O devices and traces are not part of the state.
+#[derive(Debug, Clone)]
+pub struct Snapshot<W: Word> {
+    pub memory: Memory<W>,
+    pub i_pointer: usize,
+    pub relative_base: W,
+    pub return_control: bool,
+    pub overflow: Overflow,
+    pub steps: u64,
+    pub max_steps: Option<u64>,
+    pub halted: bool,
+    /// Input values queued, but not read yet
+    pub input: Vec<W>,
+}
+
+/// Error while reading a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The file does not start with the snapshot header.
+    NotASnapshot,
+    /// The file is written in another version of the format.
+    UnsupportedVersion(String),
+    /// The file was saved by a computer with another word type.
+    WordSize {
+        expected: u32,
+        found: u32,
+    },
+    /// An entry is missing, repeated, unknown or invalid; `line` counts from one.
+    Malformed {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "cannot read snapshot: {}", err),
+            SnapshotError::NotASnapshot => write!(f, "not an Intcode snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported snapshot version {} (supported: {})",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::WordSize { expected, found } => write!(
+                f,
+                "snapshot has {} bit words, but the computer has {} bit words",
+                found, expected
+            ),
+            SnapshotError::Malformed { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+fn join<W: Word>(values: &[W], separator: &str) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(separator)
+}
+
+fn parse<T: FromStr>(line: usize, key: &str, text: &str) -> Result<T, SnapshotError> {
+    text.parse().map_err(|_| SnapshotError::Malformed {
+        line,
+        message: format!("invalid value of {}: {:?}", key, text),
+    })
+}
+
+/// Entries of a snapshot file with the line they are found in.
+struct Entries {
+    entries: HashMap<String, (usize, String)>,
+    /// Last line of the file
+    end: usize,
+}
+
+impl Entries {
+    fn get(&self, key: &str) -> Result<(usize, &str), SnapshotError> {
+        self.entries
+            .get(key)
+            .map(|(line, value)| (*line, value.as_str()))
+            .ok_or_else(|| SnapshotError::Malformed {
+                line: self.end,
+                message: format!("missing entry {}", key),
+            })
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> Result<T, SnapshotError> {
+        let (line, value) = self.get(key)?;
+        parse(line, key, value)
+    }
+}
+
+impl<W: Word> Snapshot<W> {
+    /// Write the snapshot in the format described above.
+    pub fn write_to<Wr: Write>(&self, mut writer: Wr) -> io::Result<()> {
+        let overflow = match self.overflow {
+            Overflow::Wrapping => "wrapping",
+            Overflow::Checked => "checked",
+            Overflow::Panic => "panic",
+        };
+        let max_steps = match self.max_steps {
+            Some(max_steps) => max_steps.to_string(),
+            None => "none".to_string(),
+        };
+
+        writeln!(writer, "{} {}", MAGIC, SNAPSHOT_VERSION)?;
+        writeln!(writer, "word_bits {}", W::BITS)?;
+        writeln!(writer, "i_pointer {}", self.i_pointer)?;
+        writeln!(writer, "relative_base {}", self.relative_base)?;
+        writeln!(writer, "return_control {}", self.return_control)?;
+        writeln!(writer, "overflow {}", overflow)?;
+        writeln!(writer, "steps {}", self.steps)?;
+        writeln!(writer, "max_steps {}", max_steps)?;
+        writeln!(writer, "halted {}", self.halted)?;
+        writeln!(writer, "input {}", join(&self.input, " "))?;
+        for (address, content) in self.memory.pages() {
+            let used = content
+                .iter()
+                .rposition(|&value| value != W::default())
+                .map_or(0, |last| last + 1);
+            if used > 0 {
+                writeln!(writer, "page {} {}", address, join(&content[..used], ","))?;
+            }
+        }
+        writeln!(writer, "end")?;
+        writer.flush()
+    }
+
+    /// Read a snapshot in the format described above.
+    pub fn read_from<R: BufRead>(reader: R) -> Result<Snapshot<W>, SnapshotError> {
+        let mut lines = reader.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let version = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [MAGIC, version] => version.to_string(),
+            _ => return Err(SnapshotError::NotASnapshot),
+        };
+        if version != SNAPSHOT_VERSION.to_string() {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut entries: HashMap<String, (usize, String)> = HashMap::new();
+        // pages are parsed after the word size is checked
+        let mut pages: Vec<(usize, String)> = Vec::new();
+        let mut number = 1;
+        let mut complete = false;
+        for line in lines {
+            let line = line?;
+            number += 1;
+            let (key, value) = match line.find(' ') {
+                Some(split) => (&line[..split], line[split + 1..].trim()),
+                None => (line.trim(), ""),
+            };
+            match key {
+                "end" => {
+                    complete = true;
+                    break;
+                }
+                "page" => pages.push((number, value.to_string())),
+                "word_bits" | "i_pointer" | "relative_base" | "return_control" | "overflow"
+                | "steps" | "max_steps" | "halted" | "input" => {
+                    if entries
+                        .insert(key.to_string(), (number, value.to_string()))
+                        .is_some()
+                    {
+                        return Err(SnapshotError::Malformed {
+                            line: number,
+                            message: format!("duplicate entry {:?}", key),
+                        });
+                    }
+                }
+                _ => {
+                    return Err(SnapshotError::Malformed {
+                        line: number,
+                        message: format!("unknown entry {:?}", key),
+                    })
+                }
+            }
+        }
+        if !complete {
+            return Err(SnapshotError::Malformed {
+                line: number,
+                message: "snapshot is truncated".to_string(),
+            });
+        }
+
+        let entries = Entries {
+            entries,
+            end: number,
+        };
+        let word_bits: u32 = entries.parse("word_bits")?;
+        if word_bits != W::BITS {
+            return Err(SnapshotError::WordSize {
+                expected: W::BITS,
+                found: word_bits,
+            });
+        }
+        let mut memory = Memory::new(&[]);
+        for (line, value) in &pages {
+            let mut parts = value.splitn(2, ' ');
+            let address: usize = parse(*line, "page", parts.next().unwrap_or(""))?;
+            let values = parts.next().unwrap_or("");
+            for (offset, text) in values.split(',').enumerate() {
+                let address =
+                    address
+                        .checked_add(offset)
+                        .ok_or_else(|| SnapshotError::Malformed {
+                            line: *line,
+                            message: "page exceeds the address space".to_string(),
+                        })?;
+                memory.write(address, parse(*line, "page", text)?);
+            }
+        }
+        let (overflow_line, overflow) = entries.get("overflow")?;
+        let overflow = match overflow {
+            "wrapping" => Overflow::Wrapping,
+            "checked" => Overflow::Checked,
+            "panic" => Overflow::Panic,
+            other => {
+                return Err(SnapshotError::Malformed {
+                    line: overflow_line,
+                    message: format!("invalid value of overflow: {:?}", other),
+                })
+            }
+        };
+        let max_steps = match entries.get("max_steps")?.1 {
+            "none" => None,
+            _ => Some(entries.parse("max_steps")?),
+        };
+        let (input_line, input) = entries.get("input")?;
+        let input = input
+            .split_whitespace()
+            .map(|text| parse(input_line, "input", text))
+            .collect::<Result<Vec<W>, _>>()?;
+
+        Ok(Snapshot {
+            memory,
+            i_pointer: entries.parse("i_pointer")?,
+            relative_base: entries.parse("relative_base")?,
+            return_control: entries.parse("return_control")?,
+            overflow,
+            steps: entries.parse("steps")?,
+            max_steps,
+            halted: entries.parse("halted")?,
+            input,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::intcode_cmp::{IntCodeComputer, RunState};
+    use crate::intcode_io::{InputDevice, OutputDevice};
+    use crate::intcode_mem::PAGE_SIZE;
+    use crate::intcode_snapshot::SnapshotError;
+
+    // count down from the input value to one
+    const COUNTDOWN: [i64; 13] = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+
+    fn save<I: InputDevice<i64>, O: OutputDevice<i64>>(icc: &IntCodeComputer<i64, I, O>) -> String {
+        let mut file = Vec::new();
+        icc.save(&mut file).unwrap();
+        String::from_utf8(file).unwrap()
+    }
+
+    #[test]
+    fn file_format() {
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.input.push_back(5);
+        icc.step().unwrap();
+        icc.input.extend(vec![7, 8]);
+        icc.patch_memory(3 * PAGE_SIZE + 1, -4);
+
+        let expected = format!(
+            "INTCODE-SNAPSHOT 1
+word_bits 64
+i_pointer 2
+relative_base 0
+return_control false
+overflow checked
+steps 1
+max_steps none
+halted false
+input 7 8
+page 0 3,12,4,12,1001,12,-1,12,1005,12,2,99,5
+page {} 0,-4
+end
+",
+            3 * PAGE_SIZE
+        );
+        assert_eq!(expected, save(&icc));
+    }
+
+    #[test]
+    fn resume_at_input() {
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.set_return_control(true);
+        icc.set_max_steps(Some(1000));
+        assert_eq!(Ok(RunState::AwaitingInput), icc.compute());
+        let file = save(&icc);
+
+        let mut restored = IntCodeComputer::<i64>::restore(Cursor::new(&file)).unwrap();
+        assert_eq!(file, save(&restored));
+
+        for icc in &mut [&mut icc, &mut restored] {
+            icc.input.push_back(3);
+            assert_eq!(Ok(RunState::Halted), icc.compute());
+        }
+        assert_eq!(icc.output, restored.output);
+        assert_eq!(icc.steps(), restored.steps());
+    }
+
+    #[test]
+    fn pending_input() {
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.input.extend(vec![2, 42]);
+        let file = save(&icc);
+        let mut restored = IntCodeComputer::<i64>::restore(Cursor::new(file)).unwrap();
+        restored.compute().unwrap();
+        assert_eq!(
+            vec![2, 1],
+            restored.output.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![42], restored.input.iter().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rejected_files() {
+        let file = save(&IntCodeComputer::new(&COUNTDOWN));
+
+        let result = IntCodeComputer::<i64>::restore(Cursor::new("3,12,4,12"));
+        assert!(matches!(result, Err(SnapshotError::NotASnapshot)));
+
+        let other_version = file.replacen("SNAPSHOT 1", "SNAPSHOT 2", 1);
+        let result = IntCodeComputer::<i64>::restore(Cursor::new(other_version));
+        assert!(matches!(result, Err(SnapshotError::UnsupportedVersion(v)) if v == "2"));
+
+        let result = IntCodeComputer::<i32>::restore(Cursor::new(&file));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::WordSize {
+                expected: 32,
+                found: 64
+            })
+        ));
+
+        let truncated = file.replace("end\n", "");
+        let result = IntCodeComputer::<i64>::restore(Cursor::new(truncated));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::Malformed { line: 11, .. })
+        ));
+
+        let invalid = file.replace("steps 0", "steps many");
+        let result = IntCodeComputer::<i64>::restore(Cursor::new(invalid));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::Malformed { line: 7, .. })
+        ));
+        let beyond = file.replacen("end\n", &format!("page {} 1,2\nend\n", usize::MAX), 1);
+        let result = IntCodeComputer::<i64>::restore(Cursor::new(beyond));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::Malformed { line: 12, .. })
+        ));
+
+        let duplicate = file.replacen("end\n", "i_pointer 4\nend\n", 1);
+        let result = IntCodeComputer::<i64>::restore(Cursor::new(duplicate));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::Malformed { line: 12, .. })
+        ));
+
+        // the word size is checked before memory is parsed
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.patch_memory(12, 1 << 40);
+        let result = IntCodeComputer::<i32>::restore(Cursor::new(save(&icc)));
+        assert!(matches!(
+            result,
+            Err(SnapshotError::WordSize {
+                expected: 32,
+                found: 64
+            })
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+
+use crate::intcode_disasm::op_info;
+use crate::intcode_word::Word;
+
+/// Selection of the instructions written to a trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceFilter {
+    /// Every executed instruction.
+    All,
+    /// Input and output instructions (3 and 4).
+    IoOnly,
+    /// Conditional jumps (5 and 6), whether taken or not.
+    JumpsOnly,
+    /// Instructions located in the address range.
+    AddressRange(Range<usize>),
+}
+
+impl TraceFilter {
+    /// Whether an instruction is traced.
+    ///
+    /// * `i_pointer` - address of the instruction
+    /// * `opcode` - opcode of the instruction, without parameter modes
+    pub fn matches(&self, i_pointer: usize, opcode: i128) -> bool {
+        match self {
+            TraceFilter::All => true,
+            TraceFilter::IoOnly => opcode == 3 || opcode == 4,
+            TraceFilter::JumpsOnly => opcode == 5 || opcode == 6,
+            TraceFilter::AddressRange(range) => range.contains(&i_pointer),
+        }
+    }
+}
+
+/// Memory cell written by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite<W> {
+    pub address: usize,
+    pub old: W,
+    pub new: W,
+}
+
+/// Record of a single executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent<W> {
+    /// Number of instructions executed before this one
+    pub step: u64,
+    pub i_pointer: usize,
+    /// Raw instruction, including the parameter modes
+    pub instruction: i128,
+    /// Values of the parameters read by the instruction, after resolving their modes
+    pub operands: Vec<W>,
+    pub writes: Vec<MemoryWrite<W>>,
+    /// Target of a jump, if it is taken
+    pub jump: Option<usize>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+}
+
+impl<W: Word> TraceEvent<W> {
+    /// Format the event as a single line of JSON, e.g.
+    ///
+    /// `{"step":3,"ip":4,"opcode":1001,"op":"add","operands":[3,-1],"writes":[{"addr":12,"old":3,"new":2}]}`
+    ///
+    /// The keys `jump`, `input` and `output` are only present if the instruction jumped, read
+    /// input or wrote output. Values of any word type are written as plain JSON numbers.
+    pub fn to_json(&self) -> String {
+        let mnemonic = op_info(self.instruction % 100).map_or("???", |op| op.mnemonic);
+        let operands: Vec<String> = self.operands.iter().map(|v| v.to_string()).collect();
+        let writes: Vec<String> = self
+            .writes
+            .iter()
+            .map(|w| {
+                format!(
+                    "{{\"addr\":{},\"old\":{},\"new\":{}}}",
+                    w.address, w.old, w.new
+                )
+            })
+            .collect();
+
+        let mut json = format!(
+            "{{\"step\":{},\"ip\":{},\"opcode\":{},\"op\":\"{}\",\"operands\":[{}],\"writes\":[{}]",
+            self.step,
+            self.i_pointer,
+            self.instruction,
+            mnemonic,
+            operands.join(","),
+            writes.join(",")
+        );
+        if let Some(jump) = self.jump {
+            json += &format!(",\"jump\":{}", jump);
+        }
+        if let Some(input) = self.input {
+            json += &format!(",\"input\":{}", input);
+        }
+        if let Some(output) = self.output {
+            json += &format!(",\"output\":{}", output);
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// Writer of execution traces in the JSON Lines format, one `TraceEvent` per line.
+///
+/// The sink is buffered, so tracing long runs to a file stays cheap. If writing fails, tracing
+/// stops silently and the error is reported by `finish`, so a full disk does not abort the
+/// computation itself.
+///
+/// ```
+/// use std::fs::File;
+/// use intcode::intcode_cmp::IntCodeComputer;
+/// use intcode::intcode_trace::{TraceFilter, Tracer};
+///
+/// let path = std::env::temp_dir().join("intcode-trace-doc.jsonl");
+/// let mut icc = IntCodeComputer::new(&[1101, 2, 3, 7, 4, 7, 99, 0]);
+/// icc.set_trace(Some(Tracer::new(File::create(&path).unwrap(), TraceFilter::IoOnly)));
+/// icc.compute().unwrap();
+/// icc.take_trace().unwrap().finish().unwrap();
+///
+/// let trace = std::fs::read_to_string(&path).unwrap();
+/// assert_eq!(
+///     "{\"step\":1,\"ip\":4,\"opcode\":4,\"op\":\"out\",\"operands\":[5],\"writes\":[],\"output\":5}\n",
+///     trace
+/// );
+/// ```
+pub struct Tracer {
+    sink: BufWriter<Box<dyn Write + Send>>,
+    filter: TraceFilter,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// * `sink` - destination of the trace, e.g. a file
+    /// * `filter` - selection of the instructions to trace
+    pub fn new<S: Write + Send + 'static>(sink: S, filter: TraceFilter) -> Tracer {
+        Tracer {
+            sink: BufWriter::new(Box::new(sink)),
+            filter,
+            error: None,
+        }
+    }
+
+    pub fn filter(&self) -> &TraceFilter {
+        &self.filter
+    }
+
+    /// Write an event to the sink, unless a previous write failed.
+    pub fn record<W: Word>(&mut self, event: &TraceEvent<W>) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.sink, "{}", event.to_json()) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /// Flush the trace and return the sink, or the first error that occurred while writing.
+    pub fn finish(mut self) -> io::Result<Box<dyn Write + Send>> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.sink.into_inner().map_err(|err| err.into_error())
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("filter", &self.filter)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use crate::intcode_cmp::IntCodeComputer;
+    use crate::intcode_trace::{TraceFilter, Tracer};
+
+    // count down from the input value to one
+    const COUNTDOWN: [i64; 13] = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+
+    /// Sink, which can still be read after the tracer took ownership of it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(filter: TraceFilter) -> Vec<String> {
+        let buffer = SharedBuffer::default();
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.input.push_back(2);
+        icc.set_trace(Some(Tracer::new(buffer.clone(), filter)));
+        icc.compute().unwrap();
+        icc.take_trace().unwrap().finish().unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn full_trace() {
+        let lines = trace(TraceFilter::All);
+        assert_eq!(8, lines.len());
+        assert_eq!(
+            r#"{"step":0,"ip":0,"opcode":3,"op":"in","operands":[],"writes":[{"addr":12,"old":0,"new":2}],"input":2}"#,
+            lines[0]
+        );
+        assert_eq!(
+            r#"{"step":2,"ip":4,"opcode":1001,"op":"add","operands":[2,-1],"writes":[{"addr":12,"old":2,"new":1}]}"#,
+            lines[2]
+        );
+        assert_eq!(
+            r#"{"step":3,"ip":8,"opcode":1005,"op":"jnz","operands":[1,2],"writes":[],"jump":2}"#,
+            lines[3]
+        );
+        assert_eq!(
+            r#"{"step":7,"ip":11,"opcode":99,"op":"hlt","operands":[],"writes":[]}"#,
+            lines[7]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let lines = trace(TraceFilter::IoOnly);
+        assert_eq!(3, lines.len());
+        assert!(lines[1].ends_with(r#""output":2}"#));
+
+        // the second jump is not taken
+        let lines = trace(TraceFilter::JumpsOnly);
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with(r#""jump":2}"#));
+        assert!(lines[1].ends_with(r#""writes":[]}"#));
+
+        let lines = trace(TraceFilter::AddressRange(2..5));
+        assert_eq!(4, lines.len());
+        assert!(lines
+            .iter()
+            .all(|l| l.contains(r#""ip":2,"#) || l.contains(r#""ip":4,"#)));
+    }
+
+    #[test]
+    fn failing_sink() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // the computation is not affected, the error is reported at the end
+        let mut icc = IntCodeComputer::new(&COUNTDOWN);
+        icc.input.push_back(2000);
+        icc.set_trace(Some(Tracer::new(Broken, TraceFilter::All)));
+        icc.compute().unwrap();
+        assert_eq!(2000, icc.output.len());
+        assert!(icc.take_trace().unwrap().finish().is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Word type of an Intcode computer, i.e. the type of every memory cell and I/O value.
+///
+/// Implemented for `i32`, `i64` and `i128`. Instructions and addresses are decoded on the widest
+/// type (`i128`), so the decoding logic is shared by all word sizes.
+pub trait Word:
+    Copy
+    + Default
+    + Eq
+    + Ord
+    + Hash
+    + fmt::Debug
+    + fmt::Display
+    + FromStr
+    + From<i32>
+    + Send
+    + Sync
+    + 'static
+{
+    /// Number of bits of the word type
+    const BITS: u32;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    /// Widen the word to `i128`.
+    fn to_i128(self) -> i128;
+
+    /// Narrow an `i128` to the word type, if it fits.
+    fn from_i128(value: i128) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn from_i128(value: i128) -> Option<Self> {
+                    <$t>::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(i32, i64, i128);
@@ -0,0 +1,21 @@
+//! Intcode computer from AdventOfCode 2019, shared by the puzzle solutions of days 2, 5 and 7.
+
+pub mod intcode_ascii;
+pub mod intcode_asm;
+pub mod intcode_cfg;
+pub mod intcode_circuit;
+pub mod intcode_cmp;
+pub mod intcode_debug;
+mod intcode_decode;
+pub mod intcode_disasm;
+pub mod intcode_error;
+pub mod intcode_fuzz;
+pub mod intcode_io;
+mod intcode_loop;
+pub mod intcode_mem;
+pub mod intcode_opcodes;
+pub mod intcode_parse;
+pub mod intcode_profile;
+pub mod intcode_snapshot;
+pub mod intcode_trace;
+pub mod intcode_word;